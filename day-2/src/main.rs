@@ -1,36 +1,47 @@
-use machine::IntCode;
-use utils::{input, split};
+use machine::{Image, IntCode, Program};
+use std::process::exit;
+use utils::input;
 
 fn main() {
-    let buf = split(input(), ",");
+    let prog: Program<i32> = match input().parse() {
+        Ok(prog) => prog,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    };
 
-    part2(&buf);
+    part2(&Image::from(prog));
 }
 
 #[allow(dead_code)]
-fn part1(bin: &Vec<i32>) {
-    let mut buf = bin.clone();
-    let mut machine = IntCode::new(&mut buf);
+fn part1(image: &Image<i32>) {
+    let mut machine = IntCode::new(image, vec![]);
 
     machine[1] = 12;
     machine[2] = 2;
 
-    machine.run();
+    if let Err(err) = machine.run() {
+        eprintln!("{}", err);
+        return;
+    }
 
     println!("{:?}", machine[0]);
 }
 
 #[allow(dead_code)]
-fn part2(bin: &Vec<i32>) {
+fn part2(image: &Image<i32>) {
     for noun in 0..=99 {
         for verb in 0..=99 {
-            let mut buf = bin.clone();
-            let mut machine = IntCode::new(&mut buf);
+            let mut machine = IntCode::new(image, vec![]);
 
             machine[1] = noun;
             machine[2] = verb;
+            machine.set_budget(Some(100_000));
 
-            machine.run();
+            if machine.run().is_err() || !machine.halted() {
+                continue;
+            }
 
             if machine[0] == 19690720 {
                 println!("Answer found: {}", noun * 100 + verb);
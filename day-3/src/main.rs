@@ -47,7 +47,7 @@ impl FromStr for Movement {
             Some('L') => Ok(Direction::Left),
             Some('D') => Ok(Direction::Down),
             Some(otherwise) => Err(format!("Invalid Direction: {:?}", otherwise)),
-            None => Err(format!("Expected a nonempty string")),
+            None => Err("Expected a nonempty string".to_string()),
         }?;
 
         let rest = chars.as_str();
@@ -132,11 +132,11 @@ fn dist(first: Vec<Movement>, second: Vec<Movement>) -> i32 {
     let fs = stretches(first);
     let ss = stretches(second);
 
-    let mut min_dist = i32::max_value();
+    let mut min_dist = i32::MAX;
 
     for f in &fs {
         for s in &ss {
-            if let Some(Position(y, x)) = f.intersection(&s) {
+            if let Some(Position(y, x)) = f.intersection(s) {
                 if y == 0 && x == 0 {
                     continue;
                 }
@@ -157,7 +157,7 @@ fn hop(first: Vec<Movement>, second: Vec<Movement>) -> i32 {
     let fs = dir_stretches(first);
     let ss = dir_stretches(second);
 
-    let mut min_dist = i32::max_value();
+    let mut min_dist = i32::MAX;
 
     let mut f_len = 0;
     for (fdir, f) in &fs {
@@ -167,7 +167,7 @@ fn hop(first: Vec<Movement>, second: Vec<Movement>) -> i32 {
         for (sdir, s) in &ss {
             s_len += s.len() as i32;
 
-            if let Some(Position(y, x)) = f.intersection(&s) {
+            if let Some(Position(y, x)) = f.intersection(s) {
                 if y == 0 && x == 0 {
                     continue;
                 }
@@ -110,7 +110,7 @@ impl<'a> PartialOrd for Pass<'a> {
             }
         }
 
-        return Some(std::cmp::Ordering::Equal);
+        Some(std::cmp::Ordering::Equal)
     }
 }
 
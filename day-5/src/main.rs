@@ -1,9 +1,23 @@
-use machine::IntCode;
-use utils::{input, split};
+use machine::{IntCode, Program};
+use std::process::exit;
+use utils::input;
 
 fn main() {
-    let buf = split(input(), ",");
-    let mut machine = IntCode::new(buf, vec![5]);
+    let prog: Program<i32> = match input().parse() {
+        Ok(prog) => prog,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    };
 
-    println!("Output: {:?}", machine.run());
+    for output in IntCode::new(prog, vec![5]) {
+        match output {
+            Ok(value) => println!("Output: {}", value),
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(1);
+            }
+        }
+    }
 }
@@ -1,69 +1,78 @@
 use itertools::Itertools;
-use machine::IntCode;
-use utils::{input, split};
+use machine::sched::Scheduler;
+use machine::{Image, IntCode, Program};
+use std::process::exit;
+use utils::input;
 
 fn main() {
-    let prog: Vec<i32> = split(input(), ",");
+    let prog: Program<i32> = match input().parse() {
+        Ok(prog) => prog,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    };
 
-    let amp = part_two(&prog);
+    let amp = part_two(&Image::from(prog));
     println!("amplitude: {}", amp);
 }
 
 #[allow(dead_code)]
-fn part_one(prog: &Vec<i32>) -> i32 {
+fn part_one(prog: &Image<i32>) -> i32 {
     let mut max = 0;
 
     for combs in (0..5).permutations(5) {
-        max = i32::max(max, run_amps(&prog, combs.clone()));
+        max = i32::max(max, run_amps(prog, combs.clone()));
     }
 
     max
 }
 
 #[allow(dead_code)]
-fn part_two(prog: &Vec<i32>) -> i32 {
+fn part_two(prog: &Image<i32>) -> i32 {
     let mut max = 0;
 
     for combs in (5..10).permutations(5) {
-        max = i32::max(max, run_amps_feedback(&prog, combs.clone()));
+        max = i32::max(max, run_amps_feedback(prog, combs.clone()));
     }
 
     max
 }
 
-fn run_amps_feedback(code: &Vec<i32>, mut phases: Vec<i32>) -> i32 {
-    phases.reverse();
-    let mut amp_level = 0;
-
-    'root: loop {
-        for phase in &phases {
-            dbg!(&phase, &amp_level);
-
-            let mut machine = IntCode::new(code.clone(), vec![*phase, amp_level]);
-            let out = machine.run();
+fn run_amps_feedback(code: &Image<i32>, phases: Vec<i32>) -> i32 {
+    let mut sched = Scheduler::new();
+    let amps: Vec<usize> = phases
+        .iter()
+        .map(|phase| sched.add(IntCode::new(code, vec![*phase])))
+        .collect();
 
-            dbg!(&out);
-            if let Some(amp) = out.get(0) {
-                amp_level = *amp;
-            } else {
-                break 'root;
-            }
-        }
+    for pair in amps.windows(2) {
+        sched.connect(pair[0], pair[1]);
     }
 
-    dbg!(&amp_level);
+    sched.connect(amps[amps.len() - 1], amps[0]);
+    sched.push_input(amps[0], 0);
 
-    amp_level
+    let halted = sched.run();
+    assert!(
+        halted,
+        "amplifiers stopped unexpectedly:\n{}",
+        sched.report()
+    );
+
+    *sched
+        .last_output(amps[amps.len() - 1])
+        .expect("amplifiers produced no signal")
 }
 
-fn run_amps(code: &Vec<i32>, mut phases: Vec<i32>) -> i32 {
+fn run_amps(code: &Image<i32>, mut phases: Vec<i32>) -> i32 {
     phases.reverse();
     let mut amp_level = 0;
     while let Some(phase) = phases.pop() {
         dbg!(&phase, &amp_level);
 
-        let mut machine = IntCode::new(code.clone(), vec![phase, amp_level]);
-        let out = machine.run();
+        let mut machine = IntCode::new(code, vec![phase, amp_level]);
+        let out = machine.run().unwrap();
 
         dbg!(&out);
         amp_level = out[0];
@@ -78,11 +87,22 @@ mod tests {
 
     #[test]
     fn x() {
-        let prog = vec![
+        let prog = Image::from(vec![
             3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
-        ];
+        ]);
         let phases = vec![4, 3, 2, 1, 0];
 
         assert_eq!(run_amps(&prog, phases), 43210);
     }
+
+    #[test]
+    fn feedback() {
+        let prog = Image::from(vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ]);
+        let phases = vec![9, 8, 7, 6, 5];
+
+        assert_eq!(run_amps_feedback(&prog, phases), 139629729);
+    }
 }
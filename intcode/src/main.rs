@@ -0,0 +1,128 @@
+mod tui;
+
+use machine::debug::Debugger;
+use machine::device::{Stdin, Stdout};
+use machine::{Coverage, Halt, IntCode, Memory, Program};
+use std::env;
+use std::io;
+use std::path::Path;
+use std::process::exit;
+
+const USAGE: &str = "usage: intcode (debug | tui | profile) <program>\n       intcode coverage <program> <coverage-file>";
+
+/// Hot spots and cells shown by `intcode profile`.
+const PROFILE_TOP: usize = 20;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<&str>>()
+        .as_slice()
+    {
+        ["debug", path] => debug(path),
+        ["tui", path] => tui(path),
+        ["profile", path] => profile(path),
+        ["coverage", path, file] => coverage(path, file),
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    }
+}
+
+fn load(path: &str) -> Program<i64> {
+    match Program::from_file(path) {
+        Ok(prog) => prog,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            exit(1);
+        }
+    }
+}
+
+fn debug(path: &str) {
+    let mut debugger = Debugger::new(IntCode::new(load(path), vec![]));
+
+    if let Err(err) = debugger.repl(io::stdin().lock(), io::stdout()) {
+        eprintln!("{}", err);
+        exit(1);
+    }
+}
+
+fn tui(path: &str) {
+    let app = tui::App::new(IntCode::new(load(path), vec![]));
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        exit(1);
+    }
+}
+
+/// Runs the program on stdin and stdout, then reports where it spent its time on stderr.
+fn profile(path: &str) {
+    let prog = load(path);
+    let image = Memory::from(prog.clone());
+
+    let mut machine = IntCode::new(prog, vec![]);
+    machine.input_from(Stdin);
+    machine.output_to(Stdout);
+    machine.record_profile(true);
+
+    match machine.exec() {
+        Ok(Halt::Quit) => {}
+        Ok(halt) => eprintln!("stopped: {:?}", halt),
+        Err(err) => eprintln!("error at {}", err),
+    }
+
+    if let Some(profile) = machine.profile() {
+        eprint!("{}", profile.report(&image, PROFILE_TOP));
+    }
+}
+
+/// Runs the program on stdin and stdout, merges its coverage into `file`,
+/// then prints the annotated listing and a JSON summary on stderr.
+fn coverage(path: &str, file: &str) {
+    let prog = load(path);
+
+    let mut total = if Path::new(file).exists() {
+        match Coverage::load(file) {
+            Ok(coverage) => coverage,
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                exit(1);
+            }
+        }
+    } else {
+        Coverage::default()
+    };
+
+    let mut machine = IntCode::new(prog.clone(), vec![]);
+    machine.input_from(Stdin);
+    machine.output_to(Stdout);
+    machine.record_coverage(true);
+
+    match machine.exec() {
+        Ok(Halt::Quit) => {}
+        Ok(halt) => eprintln!("stopped: {:?}", halt),
+        Err(err) => eprintln!("error at {}", err),
+    }
+
+    if let Some(coverage) = machine.coverage() {
+        total.merge(coverage);
+    }
+
+    if let Err(err) = total.save(file) {
+        eprintln!("{}: {}", file, err);
+        exit(1);
+    }
+
+    eprint!("{}", total.listing(&prog));
+    eprintln!("{}", total.summary(&prog).to_json());
+}
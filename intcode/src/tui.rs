@@ -0,0 +1,542 @@
+//! Full screen debugger.
+//!
+//! The disassembly and trace panes are rendered through `Listing` and
+//! `Record` so they read the same as `intcode debug` and trace output.
+
+use machine::debug::HISTORY;
+use machine::disasm::{disassemble_from, Item};
+use machine::trace::Ring;
+use machine::{Halt, Hex, IntCode, Word};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+use std::time::Duration;
+
+/// Instructions executed between redraws while continuing.
+const SLICE: u64 = 10_000;
+
+/// Memory cells scrolled by a page key.
+const PAGE: usize = 64;
+
+/// Most memory disassembled, so sparse programs with far
+/// writes do not get materialized in full.
+const IMAGE_LIMIT: usize = 1 << 16;
+
+const KEYS: &str = "s step  c continue  r/R step/continue back  b breakpoint  i input  up/down select  g goto ip  pgup/pgdn memory  q quit";
+
+pub struct App<W = i64> {
+    machine: IntCode<W>,
+    trace: Ring<W>,
+    outputs: Vec<W>,
+
+    /// Selected disassembly address, breakpoints are toggled here.
+    cursor: usize,
+    /// Address of the first memory row shown.
+    mem_top: usize,
+
+    /// Input being typed, if in input mode.
+    editing: Option<String>,
+    running: bool,
+    done: bool,
+    status: String,
+}
+
+impl<W: Word> App<W> {
+    pub fn new(mut machine: IntCode<W>) -> App<W> {
+        let trace = Ring::new(64);
+        machine.trace(trace.clone());
+        machine.record_recent_history(HISTORY);
+
+        App {
+            cursor: machine.ip(),
+            machine,
+            trace,
+            outputs: Vec::new(),
+            mem_top: 0,
+            editing: None,
+            running: false,
+            done: false,
+            status: String::new(),
+        }
+    }
+
+    pub fn run(mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.done {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if self.running {
+                if event::poll(Duration::ZERO)? {
+                    self.event(event::read()?);
+                } else {
+                    self.tick();
+                }
+            } else {
+                self.event(event::read()?);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn event(&mut self, event: Event) {
+        if let Event::Key(key) = event {
+            if key.kind == KeyEventKind::Press {
+                self.key(key.code);
+            }
+        }
+    }
+
+    fn key(&mut self, code: KeyCode) {
+        if let Some(text) = &mut self.editing {
+            match code {
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Enter => {
+                    match text.trim().parse() {
+                        Ok(value) => self.machine.push_input(value),
+                        Err(_) => self.status = format!("invalid input {}", text.trim()),
+                    }
+
+                    self.editing = None;
+                }
+                KeyCode::Esc => self.editing = None,
+                _ => {}
+            }
+
+            return;
+        }
+
+        if self.running {
+            self.running = false;
+            self.status = "paused".to_string();
+            self.cursor = self.machine.ip();
+            return;
+        }
+
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.done = true,
+            KeyCode::Char('s') => self.step(),
+            KeyCode::Char('c') => {
+                self.running = true;
+                self.status = "running, any key pauses".to_string();
+            }
+            KeyCode::Char('r') => self.back(false),
+            KeyCode::Char('R') => self.back(true),
+            KeyCode::Char('b') => self.toggle_breakpoint(),
+            KeyCode::Char('i') => self.editing = Some(String::new()),
+            KeyCode::Char('g') => self.cursor = self.machine.ip(),
+            KeyCode::Up => self.select(-1),
+            KeyCode::Down => self.select(1),
+            KeyCode::PageUp => self.mem_top = self.mem_top.saturating_sub(PAGE),
+            KeyCode::PageDown => self.mem_top += PAGE,
+            _ => {}
+        }
+    }
+
+    fn toggle_breakpoint(&mut self) {
+        if !self.machine.clear_breakpoint(self.cursor) {
+            self.machine.set_breakpoint(self.cursor);
+        }
+    }
+
+    fn step(&mut self) {
+        match self.machine.step() {
+            Ok(None) => self.status.clear(),
+            Ok(Some(halt)) => self.halted(halt),
+            Err(err) => self.status = format!("error at {}", err),
+        }
+
+        self.cursor = self.machine.ip();
+    }
+
+    /// Undoes the last instruction, or all of them back to a breakpoint.
+    fn back(&mut self, to_breakpoint: bool) {
+        let stopped = if to_breakpoint {
+            self.machine.exec_back()
+        } else {
+            self.machine.step_back()
+        };
+
+        self.status = match (stopped, to_breakpoint) {
+            (false, _) => "start of history".to_string(),
+            (true, true) => "breakpoint".to_string(),
+            (true, false) => String::new(),
+        };
+        self.cursor = self.machine.ip();
+    }
+
+    /// Executes the next slice of instructions while continuing.
+    ///
+    /// A budget the machine already had keeps counting down across slices.
+    fn tick(&mut self) {
+        let budget = self.machine.budget();
+        let slice = budget.map_or(SLICE, |left| left.min(SLICE));
+        let last = budget.is_some_and(|left| left <= SLICE);
+        self.machine.set_budget(Some(slice));
+
+        loop {
+            match self.machine.exec() {
+                Ok(Halt::Budget) if last => {
+                    self.halted(Halt::Budget);
+                    self.running = false;
+                    break;
+                }
+                Ok(Halt::Budget) => break,
+                Ok(Halt::Output(value)) => self.outputs.push(value),
+                Ok(halt) => {
+                    self.halted(halt);
+                    self.running = false;
+                    break;
+                }
+                Err(err) => {
+                    self.status = format!("error at {}", err);
+                    self.running = false;
+                    break;
+                }
+            }
+        }
+
+        let used = slice - self.machine.budget().unwrap_or(0);
+        self.machine.set_budget(budget.map(|left| left - used));
+        self.cursor = self.machine.ip();
+    }
+
+    fn halted(&mut self, halt: Halt<W>) {
+        self.status = match halt {
+            Halt::Output(value) => {
+                self.outputs.push(value);
+                String::new()
+            }
+            Halt::Input => "waiting for input".to_string(),
+            Halt::Quit => "quit".to_string(),
+            Halt::Budget => "budget exhausted".to_string(),
+            Halt::Loop => "infinite loop detected".to_string(),
+            Halt::Breakpoint => "breakpoint".to_string(),
+            Halt::Watch(hit) => format!("watchpoint {}", hit),
+        };
+    }
+
+    /// Moves the disassembly selection by `delta` lines.
+    fn select(&mut self, delta: isize) {
+        let (addrs, _) = self.listing();
+        let index = addrs
+            .iter()
+            .rposition(|addr| *addr <= self.cursor)
+            .unwrap_or(0);
+        let index = (index as isize + delta).clamp(0, addrs.len() as isize - 1);
+
+        if let Some(addr) = addrs.get(index as usize) {
+            self.cursor = *addr;
+        }
+    }
+
+    /// Start addresses and text of each disassembly line.
+    fn listing(&self) -> (Vec<usize>, Vec<(String, bool)>) {
+        let mem = self.machine.memory();
+        let image: Vec<W> = (0..mem.len().min(IMAGE_LIMIT))
+            .map(|addr| mem.get(addr).clone())
+            .collect();
+
+        let mut entries = vec![0, self.machine.ip()];
+        entries.extend(self.machine.breakpoints());
+
+        let listing = disassemble_from(&image, &entries);
+        let text = listing.to_string();
+
+        let addrs = listing.lines.iter().map(|line| line.addr).collect();
+        let lines = listing
+            .lines
+            .iter()
+            .zip(text.lines())
+            .map(|(line, text)| {
+                let is_instr = matches!(line.item, Item::Instr(_));
+                (text.to_string(), is_instr)
+            })
+            .collect();
+
+        (addrs, lines)
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, io, bar] = Layout::vertical([
+            Constraint::Min(8),
+            Constraint::Length(10),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [code, mem] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(main);
+        let [input, output, trace] = Layout::horizontal([
+            Constraint::Length(20),
+            Constraint::Length(20),
+            Constraint::Min(20),
+        ])
+        .areas(io);
+
+        self.draw_code(frame, code);
+        self.draw_memory(frame, mem);
+        self.draw_input(frame, input);
+        self.draw_output(frame, output);
+        self.draw_trace(frame, trace);
+
+        let status = match &self.editing {
+            Some(_) => "enter pushes the value  esc cancels".to_string(),
+            None if self.status.is_empty() => KEYS.to_string(),
+            None => format!("{}  |  {}", self.status, KEYS),
+        };
+        frame.render_widget(Paragraph::new(status), bar);
+    }
+
+    /// Disassembly centered on the selected line, which follows ip.
+    fn draw_code(&self, frame: &mut Frame, area: Rect) {
+        let ip = self.machine.ip();
+        let (addrs, text) = self.listing();
+        let height = area.height.saturating_sub(2) as usize;
+
+        let contains = |index: usize, addr: usize| {
+            addrs[index] <= addr && addrs.get(index + 1).is_none_or(|next| addr < *next)
+        };
+
+        let selected = (0..addrs.len())
+            .find(|index| contains(*index, self.cursor))
+            .unwrap_or(0);
+        let first = selected
+            .saturating_sub(height / 2)
+            .min(addrs.len().saturating_sub(height));
+
+        let breaks: Vec<usize> = self.machine.breakpoints().collect();
+        let lines: Vec<Line> = (first..addrs.len().min(first + height))
+            .map(|index| {
+                let addr = addrs[index];
+                let at_ip = !self.machine.halted() && contains(index, ip);
+                let marker = match (at_ip, breaks.contains(&addr)) {
+                    (true, true) => ">*",
+                    (true, false) => "> ",
+                    (false, true) => " *",
+                    (false, false) => "  ",
+                };
+
+                let (body, is_instr) = &text[index];
+                let mut spans = vec![
+                    Span::raw(format!("{} 0x{:04x}  ", marker, addr)),
+                    Span::raw(body.clone()),
+                ];
+
+                if at_ip && *is_instr {
+                    if let Ok(op) = self.machine.decode(ip) {
+                        spans.push(Span::styled(
+                            format!("  ; {}", op.real(&self.machine)),
+                            Style::new().fg(Color::Cyan),
+                        ));
+                    }
+                }
+
+                let mut style = Style::new();
+                if at_ip {
+                    style = style.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+                }
+                if index == selected {
+                    style = style.bg(Color::DarkGray);
+                }
+
+                Line::from(spans).style(style)
+            })
+            .collect();
+
+        let block = Block::bordered().title(" Disassembly ");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    /// Memory rows, with cells written by the last instruction in red
+    /// and those written by recent ones in yellow.
+    fn draw_memory(&self, frame: &mut Frame, area: Rect) {
+        let records = self.trace.records();
+        let latest: Vec<usize> = match records.last() {
+            Some(record) => record.writes.iter().map(|(addr, _)| *addr).collect(),
+            None => Vec::new(),
+        };
+        let recent: Vec<usize> = records
+            .iter()
+            .flat_map(|record| record.writes.iter().map(|(addr, _)| *addr))
+            .collect();
+
+        let height = area.height.saturating_sub(2) as usize;
+        let width = (area.width.saturating_sub(10) as usize / 8).clamp(1, 16);
+        let lines: Vec<Line> = (0..height)
+            .map(|row| {
+                let start = self.mem_top + row * width;
+                let mut spans = vec![Span::raw(format!("%0x{:04x}:", start))];
+
+                for addr in start..start + width {
+                    let cell = format!(" {:>7}", Hex(&self.machine[addr]).to_string());
+
+                    let style = if latest.contains(&addr) {
+                        Style::new().fg(Color::Red).add_modifier(Modifier::BOLD)
+                    } else if recent.contains(&addr) {
+                        Style::new().fg(Color::Yellow)
+                    } else {
+                        Style::new()
+                    };
+
+                    spans.push(Span::styled(cell, style));
+                }
+
+                Line::from(spans)
+            })
+            .collect();
+
+        let block = Block::bordered().title(" Memory ");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_input(&self, frame: &mut Frame, area: Rect) {
+        let mut lines: Vec<Line> = self
+            .machine
+            .pending_input()
+            .map(|value| Line::raw(value.to_string()))
+            .collect();
+
+        if let Some(text) = &self.editing {
+            lines.push(Line::styled(
+                format!("> {}_", text),
+                Style::new().fg(Color::Green),
+            ));
+        }
+
+        let block = Block::bordered().title(" Input ");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_output(&self, frame: &mut Frame, area: Rect) {
+        let height = area.height.saturating_sub(2) as usize;
+        let skip = self.outputs.len().saturating_sub(height);
+        let lines: Vec<Line> = self.outputs[skip..]
+            .iter()
+            .map(|value| Line::raw(value.to_string()))
+            .collect();
+
+        let block = Block::bordered().title(" Output ");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_trace(&self, frame: &mut Frame, area: Rect) {
+        let records = self.trace.records();
+        let height = area.height.saturating_sub(2) as usize;
+        let skip = records.len().saturating_sub(height);
+        let lines: Vec<Line> = records[skip..]
+            .iter()
+            .map(|record| Line::raw(record.to_string()))
+            .collect();
+
+        let block = Block::bordered().title(" Trace ");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn doubler() -> Vec<i64> {
+        machine::asm::assemble(
+            "
+            ; doubles each input until it reads a zero
+            start:  IN   %100
+                    JF   %100, end
+                    MULT %100, 2, %100
+                    OUT  %100
+                    JT   1, start
+            end:    QT
+            ",
+        )
+        .unwrap()
+    }
+
+    fn screen(app: &App<i64>) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(buffer.area.width as usize)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn keys() {
+        let mut app = App::new(IntCode::new(doubler(), vec![]));
+
+        for code in [KeyCode::Down, KeyCode::Char('b'), KeyCode::Char('c')] {
+            app.key(code);
+        }
+        app.tick();
+        assert_eq!(app.status, "waiting for input");
+
+        for code in "i21\n".chars() {
+            app.key(match code {
+                '\n' => KeyCode::Enter,
+                c => KeyCode::Char(c),
+            });
+        }
+        app.key(KeyCode::Char('c'));
+        app.tick();
+
+        assert_eq!(app.status, "breakpoint");
+        assert_eq!(app.machine.ip(), 2);
+        assert_eq!(app.outputs, Vec::<i64>::new());
+
+        app.key(KeyCode::Char('s'));
+        app.key(KeyCode::Char('s'));
+        assert_eq!(app.machine.ip(), 9);
+        assert_eq!(app.machine[100], 42);
+
+        app.key(KeyCode::Char('r'));
+        assert_eq!(app.machine[100], 21);
+        app.key(KeyCode::Char('R'));
+        assert_eq!(app.machine.ip(), 2);
+        app.key(KeyCode::Char('R'));
+        assert_eq!(app.status, "start of history");
+    }
+
+    #[test]
+    fn keeps_budget() {
+        let mut spin = IntCode::new(vec![1105, 1, 0], vec![]);
+        spin.set_budget(Some(SLICE + 5));
+        let mut app = App::new(spin);
+
+        app.key(KeyCode::Char('c'));
+        app.tick();
+        assert!(app.running);
+        assert_eq!(app.machine.budget(), Some(5));
+
+        app.tick();
+        assert!(!app.running);
+        assert_eq!(app.status, "budget exhausted");
+        assert_eq!(app.machine.budget(), Some(0));
+    }
+
+    #[test]
+    fn panes() {
+        let mut app = App::new(IntCode::new(doubler(), vec![5]));
+        app.key(KeyCode::Char('s'));
+
+        let screen = screen(&app);
+
+        assert!(screen.contains("   0x0000  L0000:  IN   %0x0064 "));
+        assert!(screen.contains(">  0x0002          JF   %0x0064, L000e  ; 5, 14 "));
+        assert!(screen.contains("%0x0060:  0x0000  0x0000  0x0000  0x0000  0x0005 "));
+        assert!(screen.contains("0x0000: IN   %0x0064 [%0x0064 <- 5]"));
+    }
+}
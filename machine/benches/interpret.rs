@@ -0,0 +1,114 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use machine::{Halt, Image, IntCode};
+
+// counts %0x0010 down from 100,000, summing into %0x0011
+const COUNTDOWN: [i64; 18] = [
+    1, 16, 17, 17, 1001, 16, -1, 16, 1005, 16, 0, 4, 17, 99, 0, 0, 100_000, 0,
+];
+
+// the feedback amplifier from day 7's example
+const AMPLIFIER: [i64; 29] = [
+    3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28, 1005,
+    28, 6, 99, 0, 0, 5,
+];
+
+fn countdown(cached: bool) -> i64 {
+    let mut machine = IntCode::new(COUNTDOWN.to_vec(), vec![]);
+    machine.cache_instructions(cached);
+
+    machine.run().unwrap()[0]
+}
+
+/// Runs every phase ordering through a loop of five amplifiers, as day 7 does.
+fn amplifiers(image: &Image<i64>, cached: bool) -> i64 {
+    let mut max = 0;
+    let mut phases = [5, 6, 7, 8, 9];
+
+    for _ in 0..120 {
+        let mut amps: Vec<IntCode<i64>> = phases
+            .iter()
+            .map(|phase| {
+                let mut amp = IntCode::new(image, vec![*phase]);
+                amp.cache_instructions(cached);
+                amp
+            })
+            .collect();
+
+        let mut signal = 0;
+
+        'feedback: loop {
+            for amp in &mut amps {
+                amp.push_input(signal);
+
+                match amp.exec().unwrap() {
+                    Halt::Output(out) => signal = out,
+                    _ => break 'feedback,
+                }
+            }
+        }
+
+        max = max.max(signal);
+        next_permutation(&mut phases);
+    }
+
+    max
+}
+
+fn next_permutation(items: &mut [i64]) {
+    let i = match (1..items.len()).rev().find(|i| items[i - 1] < items[*i]) {
+        Some(i) => i,
+        None => return items.reverse(),
+    };
+
+    let j = (i..items.len())
+        .rev()
+        .find(|j| items[*j] > items[i - 1])
+        .unwrap();
+    items.swap(i - 1, j);
+    items[i..].reverse();
+}
+
+/// Loads a thousand machines from a large image, each patching and running a few instructions.
+fn spawn(image: &Image<i64>, shared: bool) -> i64 {
+    let mut sum = 0;
+
+    for noun in 0..1000 {
+        let mut machine = if shared {
+            IntCode::new(image, vec![])
+        } else {
+            IntCode::new(image.to_vec(), vec![])
+        };
+
+        machine[1] = noun;
+        machine.run().unwrap();
+        sum += machine[0];
+    }
+
+    sum
+}
+
+fn interpret(c: &mut Criterion) {
+    let mut group = c.benchmark_group("countdown");
+    group.bench_function("cached", |b| b.iter(|| countdown(true)));
+    group.bench_function("uncached", |b| b.iter(|| countdown(false)));
+    group.finish();
+
+    let image = Image::from(AMPLIFIER.to_vec());
+
+    let mut group = c.benchmark_group("amplifiers");
+    group.bench_function("cached", |b| b.iter(|| amplifiers(&image, true)));
+    group.bench_function("uncached", |b| b.iter(|| amplifiers(&image, false)));
+    group.finish();
+
+    let mut words = vec![1, 0, 0, 0, 99];
+    words.resize(16 * 1024, 1);
+    let image = Image::from(words);
+
+    let mut group = c.benchmark_group("spawn");
+    group.bench_function("shared", |b| b.iter(|| spawn(&image, true)));
+    group.bench_function("copied", |b| b.iter(|| spawn(&image, false)));
+    group.finish();
+}
+
+criterion_group!(benches, interpret);
+criterion_main!(benches);
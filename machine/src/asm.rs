@@ -0,0 +1,390 @@
+//! Assembler for the listing syntax `OpCode` is displayed in.
+//!
+//! ```text
+//! ; doubles each input until it reads a zero
+//! start:  IN   %value
+//!         JF   %value, end
+//!         MULT %value, 2, %value
+//!         OUT  %value
+//!         JT   1, start
+//! end:    QT
+//! value:  .data 0
+//! ```
+//!
+//! Operands are immediate (`0x002a`, `42`, `label`), positional
+//! (`%0x002a`, `%42`, `%label`) or relative (`%rb+0x0002`, `%rb-3`).
+//! Labels may carry an offset such as `%table+2`. `.data` emits its
+//! comma separated values as is, and `;` starts a comment.
+
+use crate::Word;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Mnemonic, opcode, parameter count and the parameter written to, if any.
+const MNEMONICS: [(&str, i32, usize, Option<usize>); 10] = [
+    ("ADD", 1, 3, Some(2)),
+    ("MULT", 2, 3, Some(2)),
+    ("IN", 3, 1, Some(0)),
+    ("OUT", 4, 1, None),
+    ("JT", 5, 2, None),
+    ("JF", 6, 2, None),
+    ("LT", 7, 3, Some(2)),
+    ("EQ", 8, 3, Some(2)),
+    ("ARB", 9, 1, None),
+    ("QT", 99, 0, None),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmError {
+    /// 1 based source line of the error.
+    pub line: usize,
+    pub message: String,
+}
+
+enum Value<W> {
+    Word(W),
+    Label(String, i32),
+}
+
+enum Operand<W> {
+    Pos(Value<W>),
+    Inter(Value<W>),
+    Rel(W),
+}
+
+enum Item<W> {
+    Instr(i32, Vec<Operand<W>>),
+    Data(Vec<Value<W>>),
+}
+
+/// Assembles listing source into a program image.
+pub fn assemble<W: Word>(src: &str) -> Result<Vec<W>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut items = Vec::new();
+    let mut addr = 0;
+
+    for (index, text) in src.lines().enumerate() {
+        let line = index + 1;
+        let err = |message: String| AsmError { line, message };
+
+        let mut text = strip_comment(text);
+
+        while let Some((head, rest)) = text.split_once(':') {
+            let name = head.trim();
+            if !is_ident(name) {
+                break;
+            }
+
+            if labels.insert(name.to_string(), addr).is_some() {
+                return Err(err(format!("duplicate label {}", name)));
+            }
+
+            text = rest.trim();
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let item = parse_item(text).map_err(err)?;
+        addr += match &item {
+            Item::Instr(_, operands) => 1 + operands.len(),
+            Item::Data(values) => values.len(),
+        };
+
+        items.push((line, item));
+    }
+
+    let mut words = Vec::with_capacity(addr);
+    for (line, item) in items {
+        let err = |message: String| AsmError { line, message };
+        let resolve = |value: Value<W>| resolve(value, &labels).map_err(err);
+
+        match item {
+            Item::Instr(opcode, operands) => {
+                let mut code = opcode;
+                let mut args = Vec::with_capacity(operands.len());
+
+                for (i, operand) in operands.into_iter().enumerate() {
+                    let (mode, arg) = match operand {
+                        Operand::Pos(value) => (0, resolve(value)?),
+                        Operand::Inter(value) => (1, resolve(value)?),
+                        Operand::Rel(offset) => (2, offset),
+                    };
+
+                    code += mode * 10i32.pow(i as u32 + 2);
+                    args.push(arg);
+                }
+
+                words.push(W::from(code));
+                words.extend(args);
+            }
+
+            Item::Data(values) => {
+                for value in values {
+                    words.push(resolve(value)?);
+                }
+            }
+        }
+    }
+
+    Ok(words)
+}
+
+/// A source line with its comment and surrounding whitespace removed.
+fn strip_comment(line: &str) -> &str {
+    let line = match line.split_once(';') {
+        Some((code, _)) => code,
+        None => line,
+    };
+
+    line.trim()
+}
+
+fn is_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && name != "rb"
+}
+
+fn parse_item<W: Word>(text: &str) -> Result<Item<W>, String> {
+    let (head, rest) = match text.split_once(char::is_whitespace) {
+        Some((head, rest)) => (head, rest.trim()),
+        None => (text, ""),
+    };
+
+    let args: Vec<&str> = if rest.is_empty() {
+        Vec::new()
+    } else {
+        rest.split(',').map(str::trim).collect()
+    };
+
+    if head == ".data" {
+        if args.is_empty() {
+            return Err(".data needs at least one value".to_string());
+        }
+
+        let values = args
+            .into_iter()
+            .map(parse_value)
+            .collect::<Result<_, _>>()?;
+        return Ok(Item::Data(values));
+    }
+
+    let mnemonic = head.to_ascii_uppercase();
+    let (_, opcode, arity, dest) = match MNEMONICS.iter().find(|(name, ..)| *name == mnemonic) {
+        Some(entry) => *entry,
+        None => return Err(format!("unknown mnemonic {}", head)),
+    };
+
+    if args.len() != arity {
+        return Err(format!(
+            "{} takes {} operands, found {}",
+            mnemonic,
+            arity,
+            args.len()
+        ));
+    }
+
+    let mut operands = Vec::with_capacity(arity);
+    for (i, arg) in args.into_iter().enumerate() {
+        let operand = parse_operand(arg)?;
+
+        if let (Operand::Inter(_), Some(dest)) = (&operand, dest) {
+            if dest == i {
+                return Err(format!("{} can not write to immediate {}", mnemonic, arg));
+            }
+        }
+
+        operands.push(operand);
+    }
+
+    Ok(Item::Instr(opcode, operands))
+}
+
+fn parse_operand<W: Word>(arg: &str) -> Result<Operand<W>, String> {
+    if let Some(offset) = arg.strip_prefix("%rb") {
+        if offset.is_empty() {
+            return Ok(Operand::Rel(W::from(0)));
+        }
+
+        let offset = offset.strip_prefix('+').unwrap_or(offset);
+        if let Some(offset) = parse_number(offset) {
+            return Ok(Operand::Rel(offset));
+        }
+    }
+
+    match arg.strip_prefix('%') {
+        Some(addr) => Ok(Operand::Pos(parse_value(addr)?)),
+        None => Ok(Operand::Inter(parse_value(arg)?)),
+    }
+}
+
+fn parse_value<W: Word>(arg: &str) -> Result<Value<W>, String> {
+    if let Some(word) = parse_number(arg) {
+        return Ok(Value::Word(word));
+    }
+
+    let (name, offset) = match arg.find(['+', '-']) {
+        Some(split) if split > 0 => {
+            let offset = arg[split..].trim_start_matches('+');
+
+            match offset.parse() {
+                Ok(offset) => (&arg[..split], offset),
+                Err(_) => return Err(format!("invalid label offset {}", arg)),
+            }
+        }
+        _ => (arg, 0),
+    };
+
+    if is_ident(name) {
+        Ok(Value::Label(name.to_string(), offset))
+    } else {
+        Err(format!("invalid operand {}", arg))
+    }
+}
+
+/// Parses decimal or `0x` hex, either optionally negative.
+///
+/// Numbers that do not fit in a word are rejected.
+pub(crate) fn parse_number<W: Word>(arg: &str) -> Option<W> {
+    let (negative, digits) = match arg.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, arg),
+    };
+
+    let hex = match digits.strip_prefix("0x") {
+        Some(hex) if !hex.is_empty() => hex,
+        Some(_) => return None,
+        None if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) => {
+            // with its sign, so the most negative word parses
+            return arg.parse().ok();
+        }
+        None => return None,
+    };
+
+    let magnitude = hex.chars().try_fold(W::from(0), |acc, c| {
+        let digit = W::from(c.to_digit(16)? as i32);
+        acc.checked_mul(&W::from(16))?.checked_add(&digit)
+    })?;
+
+    if negative {
+        magnitude.checked_mul(&W::from(-1))
+    } else {
+        Some(magnitude)
+    }
+}
+
+fn resolve<W: Word>(value: Value<W>, labels: &HashMap<String, usize>) -> Result<W, String> {
+    match value {
+        Value::Word(word) => Ok(word),
+        Value::Label(name, offset) => {
+            match labels.get(&name).and_then(|addr| W::from_addr(*addr)) {
+                Some(addr) => addr
+                    .checked_add(&W::from(offset))
+                    .ok_or_else(|| format!("label {} out of range", name)),
+                None => Err(format!("undefined label {}", name)),
+            }
+        }
+    }
+}
+
+impl Display for AsmError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        write!(fmt, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::IntCode;
+
+    #[test]
+    fn listing_syntax() {
+        let src = "
+            ADD  %0x0009, %0x000a, %0x0003
+            MULT %0x0003, %0x000b, %0x0000
+            QT
+            .data 30, 40, 50
+        ";
+
+        let prog: Vec<i64> = assemble(src).unwrap();
+
+        assert_eq!(prog, vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+    }
+
+    #[test]
+    fn modes() {
+        let prog: Vec<i64> = assemble("ADD  -0x0003, %rb-0x0010, %0x0002").unwrap();
+        assert_eq!(prog, vec![2101, -3, -16, 2]);
+
+        let prog: Vec<i64> = assemble("in %rb\narb 0x10\nout %rb+2").unwrap();
+        assert_eq!(prog, vec![203, 0, 109, 16, 204, 2]);
+    }
+
+    #[test]
+    fn labels() {
+        let src = "
+            ; doubles each input until it reads a zero
+            start:  IN   %value
+                    JF   %value, end
+                    MULT %value, 2, %value
+                    OUT  %value
+                    JT   1, start
+            end:    QT
+            value:  .data 0
+            table:  .data end, value+1, value-1
+        ";
+
+        let prog: Vec<i64> = assemble(src).unwrap();
+        assert_eq!(&prog[15..], &[0, 14, 16, 14]);
+
+        let mut machine = IntCode::new(prog, vec![3, 4, 0]);
+        assert_eq!(machine.run(), Ok(vec![6, 8]));
+        assert!(machine.halted());
+    }
+
+    #[test]
+    fn errors() {
+        let err = assemble::<i64>("QT\n\nJMP 1").unwrap_err();
+        assert_eq!(err.to_string(), "line 3: unknown mnemonic JMP");
+
+        let err = assemble::<i64>("JT 1, nowhere").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: undefined label nowhere");
+
+        let err = assemble::<i64>("a: QT\na: QT").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: duplicate label a");
+
+        let err = assemble::<i64>("ADD 1, 2, 3").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: ADD can not write to immediate 3");
+
+        let err = assemble::<i64>("OUT 1, 2").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: OUT takes 1 operands, found 2");
+
+        let err = assemble::<i64>("OUT 0xzz").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: invalid operand 0xzz");
+
+        let err = assemble::<i64>("QT\nOUT 0xfffffffffffffffff").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2: invalid operand 0xfffffffffffffffff"
+        );
+
+        let err = assemble::<i64>("OUT -99999999999999999999").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1: invalid operand -99999999999999999999"
+        );
+
+        let prog: Vec<i64> = assemble("OUT -9223372036854775808").unwrap();
+        assert_eq!(prog, vec![104, i64::MIN]);
+    }
+}
@@ -0,0 +1,169 @@
+//! Running machines as futures, behind the `async` feature.
+//!
+//! `IntCode::run_async` drives the same interpreter as `IntCode::exec`,
+//! but awaits input from a `Stream` when the input queue runs dry and sends
+//! outputs into a `Sink`, such as the ends of a `futures::channel::mpsc`
+//! channel. Long stretches of computation yield to the executor
+//! periodically, so many machines can share one executor with other tasks.
+
+use crate::sched::{run_slice, State, SLICE};
+use crate::{Halt, IntCode, MachineError, Word};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+impl<W: Word> IntCode<W> {
+    /// Runs the machine until it quits or `input` ends while input is needed,
+    /// awaiting input from `input` and sending outputs to `output`.
+    ///
+    /// Returns the halt that stopped it, like `exec` does for halts other
+    /// than output and input, breakpoints included. It yields to the
+    /// executor after each slice of instructions the scheduler would run.
+    /// Outputs are discarded once `output` is closed.
+    pub async fn run_async<I, O>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+    ) -> Result<Halt<W>, MachineError<W>>
+    where
+        I: Stream<Item = W> + Unpin,
+        O: Sink<W> + Unpin,
+    {
+        loop {
+            let mut outputs = Vec::new();
+            let (state, _) = run_slice(self, SLICE, |value| outputs.push(value));
+
+            for value in outputs {
+                let _ = output.send(value).await;
+            }
+
+            match state {
+                State::Runnable => YieldNow(false).await,
+
+                State::Blocked => match input.next().await {
+                    Some(value) => self.push_input(value),
+                    None => return Ok(Halt::Input),
+                },
+
+                State::Halted => return Ok(Halt::Quit),
+                State::Stopped(halt) => return Ok(halt),
+                State::Faulted(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Pending once, so other tasks on the executor get to run.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::DOUBLER;
+    use crate::{Halt, IntCode};
+    use futures::channel::mpsc::unbounded;
+    use futures::executor::LocalPool;
+    use futures::future::join;
+    use futures::task::LocalSpawnExt;
+    use futures::StreamExt;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn awaits_input() {
+        let (sender, mut input) = unbounded();
+        let (mut output, mut outputs) = unbounded();
+        let mut doubler = IntCode::new(DOUBLER.to_vec(), vec![]);
+
+        let machine = async move { doubler.run_async(&mut input, &mut output).await };
+
+        // each input is sent once the answer to the last is back,
+        // so the machine waits on a pending stream in between
+        let feeder = async move {
+            let mut answers = Vec::new();
+
+            for value in 1..=3 {
+                sender.unbounded_send(value).unwrap();
+                answers.push(outputs.next().await.unwrap());
+            }
+
+            answers
+        };
+
+        let (halt, answers) = LocalPool::new().run_until(join(machine, feeder));
+
+        assert_eq!(answers, vec![2, 4, 6]);
+        assert_eq!(halt, Ok(Halt::Input));
+    }
+
+    #[test]
+    fn stops_at_breakpoints() {
+        let (mut input, mut output) = (futures::stream::empty(), futures::sink::drain());
+        let mut machine = IntCode::new(vec![104, 1, 104, 2, 99], vec![]);
+        machine.set_breakpoint(2);
+
+        let mut pool = LocalPool::new();
+        let halt = pool.run_until(machine.run_async(&mut input, &mut output));
+        assert_eq!(halt, Ok(Halt::Breakpoint));
+        assert_eq!(machine.ip(), 2);
+
+        let halt = pool.run_until(machine.run_async(&mut input, &mut output));
+        assert_eq!(halt, Ok(Halt::Quit));
+    }
+
+    #[test]
+    fn yields_while_computing() {
+        let mut pool = LocalPool::new();
+        let spawner = pool.spawner();
+        let done = Rc::new(RefCell::new(Vec::new()));
+
+        let mut spin = IntCode::new(vec![1105, 1, 0], vec![]);
+        spin.set_budget(Some(50_000));
+
+        let order = done.clone();
+        spawner
+            .spawn_local(async move {
+                let (mut input, mut output) = (futures::stream::empty(), futures::sink::drain());
+                let halt = spin.run_async(&mut input, &mut output).await;
+
+                assert_eq!(halt, Ok(Halt::Budget));
+                order.borrow_mut().push("spin");
+            })
+            .unwrap();
+
+        let order = done.clone();
+        spawner
+            .spawn_local(async move {
+                let (sender, mut input) = unbounded();
+                let (mut output, outputs) = unbounded();
+                sender.unbounded_send(7).unwrap();
+                drop(sender);
+
+                let mut echo = IntCode::new(vec![3, 0, 4, 0, 3, 0, 99], vec![]);
+                let halt = echo.run_async(&mut input, &mut output).await;
+                drop(output);
+
+                assert_eq!(halt, Ok(Halt::Input));
+                assert_eq!(outputs.collect::<Vec<i64>>().await, vec![7]);
+                order.borrow_mut().push("echo");
+            })
+            .unwrap();
+
+        pool.run();
+        assert_eq!(*done.borrow(), vec!["echo", "spin"]);
+    }
+}
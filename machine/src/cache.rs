@@ -0,0 +1,188 @@
+use crate::disasm::{disassemble, Item};
+use crate::error::Fault;
+use crate::{Memory, OpCode, Word};
+use std::sync::Arc;
+
+/// Words in the longest instruction.
+const MAX_SIZE: usize = 4;
+
+/// Instructions at or past this address are decoded every time,
+/// so a jump into the far reaches of sparse memory can't grow the cache.
+const LIMIT: usize = 1 << 20;
+
+/// Decoded instructions by address.
+pub(crate) type Table<W> = Vec<Option<OpCode<W>>>;
+
+/// Decoded instructions by address, so hot code is only decoded once.
+///
+/// Machines loaded from an `Image` start out sharing the instructions
+/// decoded from it, and copy the table once they decode or drop anything
+/// themselves. An entry is dropped as soon as a write lands in any of the
+/// words it was decoded from, so self modifying code still executes what is
+/// in memory.
+#[derive(Debug, Clone)]
+pub(crate) struct Cache<W> {
+    /// None if caching is disabled.
+    entries: Option<Arc<Table<W>>>,
+}
+
+impl<W: Word> Cache<W> {
+    /// An enabled cache for `mem`, starting from its image's code if it has one.
+    pub(crate) fn new(mem: &Memory<W>) -> Cache<W> {
+        let entries = match mem.image() {
+            Some(image) => image.code(),
+            None => Arc::default(),
+        };
+
+        Cache {
+            entries: Some(entries),
+        }
+    }
+
+    pub(crate) fn enable(&mut self, enabled: bool) {
+        if !enabled {
+            self.entries = None;
+        } else if self.entries.is_none() {
+            self.entries = Some(Arc::default());
+        }
+    }
+
+    /// Decodes the instruction at `ip`, reusing an earlier decode if it is still valid.
+    pub(crate) fn decode(&mut self, mem: &Memory<W>, ip: usize) -> Result<OpCode<W>, Fault<W>> {
+        let entries = match &mut self.entries {
+            Some(entries) if ip < LIMIT => entries,
+            _ => return OpCode::decode(mem, ip),
+        };
+
+        if let Some(Some(op)) = entries.get(ip) {
+            return Ok(op.clone());
+        }
+
+        let op = OpCode::decode(mem, ip)?;
+        let entries = Arc::make_mut(entries);
+
+        if entries.len() <= ip {
+            let len = (ip + 1).max(2 * entries.len()).clamp(64, LIMIT);
+            entries.resize(len, None);
+        }
+
+        entries[ip] = Some(op.clone());
+        Ok(op)
+    }
+
+    /// Drops instructions decoded from the word at `addr`.
+    pub(crate) fn invalidate(&mut self, addr: usize) {
+        let entries = match &mut self.entries {
+            Some(entries) => entries,
+            None => return,
+        };
+
+        let start = addr.saturating_sub(MAX_SIZE - 1);
+        let end = entries.len().min(addr.saturating_add(1));
+        let covers = |ip: usize, entry: &Option<OpCode<W>>| matches!(entry, Some(op) if ip + op.size() > addr);
+
+        // writes into data leave a shared table alone
+        if start >= end || !(start..end).any(|ip| covers(ip, &entries[ip])) {
+            return;
+        }
+
+        let entries = Arc::make_mut(entries);
+        for (ip, entry) in (start..end).zip(&mut entries[start..end]) {
+            if covers(ip, entry) {
+                *entry = None;
+            }
+        }
+    }
+
+    /// Starts over from `mem`'s image, e.g. because memory was replaced.
+    pub(crate) fn reset(&mut self, mem: &Memory<W>) {
+        if self.entries.is_some() {
+            *self = Cache::new(mem);
+        }
+    }
+}
+
+/// Decodes the instructions reachable from address 0 of `words`, the way
+/// `disasm::disassemble` finds them.
+pub(crate) fn decode_code<W: Word>(words: &[W]) -> Table<W> {
+    let mut table = Table::new();
+
+    for line in disassemble(words).lines {
+        if let Item::Instr(op) = line.item {
+            table.resize(line.addr + 1, None);
+            table[line.addr] = Some(op);
+        }
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::DOUBLER;
+    use crate::{Halt, Image, IntCode};
+    use std::sync::Arc;
+
+    #[test]
+    fn self_modifying() {
+        // outputs 5, overwrites the output with a quit and loops back
+        let prog = vec![104, 5, 1101, 99, 0, 0, 1105, 1, 0];
+        let mut machine: IntCode<i64> = IntCode::new(prog, vec![]);
+
+        assert_eq!(machine.exec(), Ok(Halt::Output(5)));
+        assert_eq!(machine.exec(), Ok(Halt::Quit));
+
+        // outputs 5, then rewrites the output's operand to 6
+        let prog = vec![104, 5, 1101, 6, 0, 1, 1105, 1, 0];
+        let mut machine: IntCode<i64> = IntCode::new(prog, vec![]);
+
+        assert_eq!(machine.exec(), Ok(Halt::Output(5)));
+        assert_eq!(machine.exec(), Ok(Halt::Output(6)));
+    }
+
+    #[test]
+    fn patched_between_runs() {
+        // outputs the constant at 1 forever
+        let prog = vec![104, 7, 1105, 1, 0];
+        let mut machine: IntCode<i64> = IntCode::new(prog, vec![]);
+
+        assert_eq!(machine.exec(), Ok(Halt::Output(7)));
+        assert_eq!(machine.exec(), Ok(Halt::Output(7)));
+
+        machine[1] = 8;
+        assert_eq!(machine.exec(), Ok(Halt::Output(8)));
+
+        machine[0] = 99;
+        assert_eq!(machine.exec(), Ok(Halt::Quit));
+    }
+
+    #[test]
+    fn shared_with_image() {
+        // outputs the constant at 1 forever, with data after the code
+        let image = Image::from(vec![104, 7, 1105, 1, 0, 0, 0]);
+        let mut a: IntCode<i64> = IntCode::new(&image, vec![]);
+        let mut b: IntCode<i64> = IntCode::new(&image, vec![]);
+
+        let table = |machine: &IntCode<i64>| machine.cache.entries.clone().unwrap();
+        assert!(Arc::ptr_eq(&table(&a), &table(&b)));
+        assert_eq!(table(&a).len(), 3);
+
+        a[6] = 1;
+        assert!(Arc::ptr_eq(&table(&a), &table(&b)));
+
+        a[1] = 8;
+        assert!(!Arc::ptr_eq(&table(&a), &table(&b)));
+        assert_eq!(a.exec(), Ok(Halt::Output(8)));
+        assert_eq!(b.exec(), Ok(Halt::Output(7)));
+    }
+
+    #[test]
+    fn uncached() {
+        let mut cached: IntCode<i64> = IntCode::new(DOUBLER.to_vec(), vec![1, 2, 3, 0]);
+        let mut uncached: IntCode<i64> = IntCode::new(DOUBLER.to_vec(), vec![1, 2, 3, 0]);
+        uncached.cache_instructions(false);
+
+        assert_eq!(cached.run(), Ok(vec![2, 4, 6]));
+        assert_eq!(uncached.run(), Ok(vec![2, 4, 6]));
+    }
+}
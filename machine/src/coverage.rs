@@ -0,0 +1,327 @@
+use crate::disasm::{disassemble_from, Item};
+use crate::format::{self, join, FormatError, Lines};
+use crate::{OpCode, Param, Word};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter, Result as FmtResult, Write};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+const HEADER: &str = "intcode-coverage 1";
+
+/// Instructions and branch directions exercised, see `IntCode::record_coverage`.
+///
+/// Coverage of several runs is combined with `merge`, and kept between
+/// processes with `save` and `load` as line oriented text:
+///
+/// ```text
+/// intcode-coverage 1
+/// executed 0,2,5,9,11
+/// branches 2:j,11:jf
+/// ```
+///
+/// Each branch lists whether it jumped (`j`) and fell through (`f`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    executed: BTreeSet<usize>,
+
+    /// Directions taken by each conditional jump, as (jumped, fell through).
+    branches: BTreeMap<usize, (bool, bool)>,
+}
+
+/// Coverage of one listing line.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Status {
+    Data,
+    Missed,
+    Covered,
+
+    /// A conditional jump that only ever went one way.
+    OneWay {
+        jumped: bool,
+    },
+}
+
+/// Totals over a program's listing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub instructions: usize,
+    pub executed: usize,
+
+    /// Conditional jumps, and how many of their two directions were taken.
+    pub branches: usize,
+    pub directions: usize,
+
+    /// Instructions never executed.
+    pub missed: Vec<usize>,
+
+    /// Conditional jumps taken only one way.
+    pub one_way: Vec<usize>,
+}
+
+impl Coverage {
+    pub(crate) fn execute(&mut self, ip: usize) {
+        self.executed.insert(ip);
+    }
+
+    /// Records the direction of a jump, if its condition could go either way.
+    pub(crate) fn branch<W: Word>(&mut self, ip: usize, op: &OpCode<W>, jumped: bool) {
+        if !conditional(op) {
+            return;
+        }
+
+        let directions = self.branches.entry(ip).or_insert((false, false));
+
+        if jumped {
+            directions.0 = true;
+        } else {
+            directions.1 = true;
+        }
+    }
+
+    /// Adds the coverage of another run.
+    pub fn merge(&mut self, other: &Coverage) {
+        self.executed.extend(other.executed.iter().copied());
+
+        for (ip, (jumped, fell)) in &other.branches {
+            let directions = self.branches.entry(*ip).or_insert((false, false));
+            directions.0 |= jumped;
+            directions.1 |= fell;
+        }
+    }
+
+    /// Whether the instruction at `addr` was executed.
+    pub fn executed(&self, addr: usize) -> bool {
+        self.executed.contains(&addr)
+    }
+
+    /// Coverage of each line of the listing of `image`,
+    /// disassembled from address 0 and every executed address.
+    pub fn lines<W: Word>(&self, image: &[W]) -> Vec<(usize, String, Status)> {
+        let mut entries = vec![0];
+        entries.extend(self.executed.iter().copied());
+
+        let listing = disassemble_from(image, &entries);
+        let text = listing.to_string();
+
+        listing
+            .lines
+            .iter()
+            .zip(text.lines())
+            .map(|(line, text)| {
+                let status = match &line.item {
+                    Item::Data(_) => Status::Data,
+                    Item::Instr(_) if !self.executed(line.addr) => Status::Missed,
+                    Item::Instr(op) if conditional(op) => match self.branches.get(&line.addr) {
+                        Some((true, false)) => Status::OneWay { jumped: true },
+                        Some((false, true)) => Status::OneWay { jumped: false },
+                        _ => Status::Covered,
+                    },
+                    Item::Instr(_) => Status::Covered,
+                };
+
+                (line.addr, text.to_string(), status)
+            })
+            .collect()
+    }
+
+    /// The listing of `image` with each instruction marked as executed (`+`),
+    /// never executed (`-`) or a branch only taken one way (`~`).
+    pub fn listing<W: Word>(&self, image: &[W]) -> String {
+        let mut out = String::new();
+
+        for (addr, text, status) in self.lines(image) {
+            let (marker, note) = match status {
+                Status::Data => (' ', ""),
+                Status::Missed => ('-', ""),
+                Status::Covered => ('+', ""),
+                Status::OneWay { jumped: true } => ('~', "  ; always jumped"),
+                Status::OneWay { jumped: false } => ('~', "  ; never jumped"),
+            };
+
+            let _ = writeln!(out, "{} 0x{:04x}  {}{}", marker, addr, text, note);
+        }
+
+        out
+    }
+
+    pub fn summary<W: Word>(&self, image: &[W]) -> Summary {
+        let mut summary = Summary::default();
+
+        for (addr, _, status) in self.lines(image) {
+            if status != Status::Data {
+                summary.instructions += 1;
+            }
+
+            match status {
+                Status::Data => {}
+                Status::Missed => summary.missed.push(addr),
+                Status::Covered => summary.executed += 1,
+                Status::OneWay { .. } => {
+                    summary.executed += 1;
+                    summary.one_way.push(addr);
+                }
+            }
+        }
+
+        for (jumped, fell) in self.branches.values() {
+            summary.branches += 1;
+            summary.directions += *jumped as usize + *fell as usize;
+        }
+
+        summary
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        format::save(self, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Coverage, FormatError> {
+        format::load(path)
+    }
+}
+
+/// Whether `op` can both jump and fall through, depending on its condition.
+fn conditional<W: Word>(op: &OpCode<W>) -> bool {
+    match op {
+        OpCode::JumpTrue(cond, _) | OpCode::JumpFalse(cond, _) => !matches!(cond, Param::Inter(_)),
+        _ => false,
+    }
+}
+
+impl Summary {
+    /// The summary as a single JSON object.
+    pub fn to_json(&self) -> String {
+        let list = |addrs: &[usize]| {
+            let addrs: Vec<String> = addrs.iter().map(usize::to_string).collect();
+            addrs.join(",")
+        };
+
+        format!(
+            "{{\"instructions\":{},\"executed\":{},\"branches\":{},\"directions\":{},\"missed\":[{}],\"one_way\":[{}]}}",
+            self.instructions,
+            self.executed,
+            self.branches,
+            self.directions,
+            list(&self.missed),
+            list(&self.one_way),
+        )
+    }
+}
+
+impl Display for Coverage {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        let branches = self.branches.iter().map(|(addr, (jumped, fell))| {
+            let jumped = if *jumped { "j" } else { "" };
+            let fell = if *fell { "f" } else { "" };
+
+            format!("{}:{}{}", addr, jumped, fell)
+        });
+
+        writeln!(fmt, "{}", HEADER)?;
+        writeln!(fmt, "executed {}", join(&self.executed))?;
+        writeln!(fmt, "branches {}", join(branches))
+    }
+}
+
+impl FromStr for Coverage {
+    type Err = FormatError;
+
+    fn from_str(src: &str) -> Result<Coverage, FormatError> {
+        let mut lines = Lines::new(src);
+        let mut coverage = Coverage::default();
+        lines.header(HEADER)?;
+
+        for token in Lines::tokens(lines.field("executed")?) {
+            coverage.execute(lines.parse(token)?);
+        }
+
+        for token in Lines::tokens(lines.field("branches")?) {
+            let (addr, directions) = match token.split_once(':') {
+                Some(split) => split,
+                None => return lines.invalid(token),
+            };
+            let addr = match addr.parse() {
+                Ok(addr) => addr,
+                Err(_) => return lines.invalid(token),
+            };
+
+            if directions.is_empty() || directions.contains(|c| c != 'j' && c != 'f') {
+                return lines.invalid(token);
+            }
+
+            let entry = coverage.branches.entry(addr).or_insert((false, false));
+            entry.0 |= directions.contains('j');
+            entry.1 |= directions.contains('f');
+        }
+
+        Ok(coverage)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::DOUBLER;
+    use crate::IntCode;
+
+    fn run(input: Vec<i64>) -> Coverage {
+        let mut machine = IntCode::new(DOUBLER.to_vec(), input);
+        machine.record_coverage(true);
+        machine.run().unwrap();
+
+        machine.coverage().unwrap().clone()
+    }
+
+    #[test]
+    fn merge_runs() {
+        let mut coverage = run(vec![3]);
+
+        assert_eq!(
+            coverage.listing(&DOUBLER),
+            "+ 0x0000  L0000:  IN   %0x0064\n\
+             ~ 0x0002          JF   %0x0064, L000e  ; never jumped\n\
+             + 0x0005          MULT %0x0064, 0x0002, %0x0064\n\
+             + 0x0009          OUT  %0x0064\n\
+             + 0x000b          JT   0x0001, L0000\n\
+             - 0x000e  L000e:  QT\n"
+        );
+
+        coverage.merge(&run(vec![0]));
+        let summary = coverage.summary(&DOUBLER);
+
+        assert_eq!(summary.instructions, 6);
+        assert_eq!(summary.executed, 6);
+        assert_eq!((summary.branches, summary.directions), (1, 2));
+        assert_eq!(
+            summary.to_json(),
+            r#"{"instructions":6,"executed":6,"branches":1,"directions":2,"missed":[],"one_way":[]}"#
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let summary = run(vec![0]).summary(&DOUBLER);
+
+        assert_eq!(summary.missed, vec![5, 9, 11]);
+        assert_eq!(summary.one_way, vec![2]);
+        assert_eq!((summary.branches, summary.directions), (1, 1));
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let coverage = run(vec![4, 0]);
+        let text = coverage.to_string();
+
+        assert_eq!(
+            text,
+            "intcode-coverage 1\nexecuted 0,2,5,9,11,14\nbranches 2:jf\n"
+        );
+        assert_eq!(text.parse::<Coverage>().unwrap(), coverage);
+
+        let err = "intcode-coverage 1\nexecuted 0\nbranches 2:x\n"
+            .parse::<Coverage>()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "line 3: invalid value \"2:x\"");
+    }
+}
@@ -0,0 +1,621 @@
+//! Interactive debugger over an `IntCode` machine.
+//!
+//! Commands are read a line at a time, an empty line repeats the last one:
+//!
+//! ```text
+//! step [n]            execute n instructions (s)
+//! continue            run until a breakpoint or halt (c)
+//! rstep [n]           undo n instructions (rs)
+//! rcontinue           undo until a breakpoint or the start (rc)
+//! rewind n            undo up to the instruction giving output n
+//! break [addr]        set a breakpoint, or list them (b)
+//! delete addr         remove a breakpoint (d)
+//! watch [addr [rw]]   watch reads (r), writes (w) or both, or list them (w)
+//! watch code          stop on writes into executed code
+//! unwatch addr        remove a watchpoint
+//! mem addr [n]        show n memory cells (x)
+//! set addr value      write a memory cell
+//! input value...      queue input values (in)
+//! info                show registers and pending input (i)
+//! list [addr] [n]     disassemble n instructions (l)
+//! quit                leave the debugger (q)
+//! ```
+//!
+//! Addresses and values are decimal or `0x` hex. Outputs are numbered
+//! from 0 in the order they were produced. `mem` and `list` show at most
+//! 1,000 lines at a time.
+
+use crate::asm::parse_number;
+use crate::{Halt, IntCode, OpCode, Watch, Word};
+use std::io::{self, BufRead, Write};
+
+/// Most lines `mem` and `list` show for one command.
+const SHOW_LIMIT: usize = 1_000;
+
+const HELP: &str = "\
+step [n]            execute n instructions (s)
+continue            run until a breakpoint or halt (c)
+rstep [n]           undo n instructions (rs)
+rcontinue           undo until a breakpoint or the start (rc)
+rewind n            undo up to the instruction giving output n
+break [addr]        set a breakpoint, or list them (b)
+delete addr         remove a breakpoint (d)
+watch [addr [rw]]   watch reads (r), writes (w) or both, or list them (w)
+watch code          stop on writes into executed code
+unwatch addr        remove a watchpoint
+mem addr [n]        show n memory cells (x)
+set addr value      write a memory cell
+input value...      queue input values (in)
+info                show registers and pending input (i)
+list [addr] [n]     disassemble n instructions (l)
+quit                leave the debugger (q)";
+
+/// Instructions the debugger keeps in history to run backwards.
+pub const HISTORY: usize = 100_000;
+
+pub struct Debugger<W = i64> {
+    machine: IntCode<W>,
+    last: String,
+}
+
+impl<W: Word> Debugger<W> {
+    /// Wraps `machine`, recording the last `HISTORY` instructions
+    /// so it can be run backwards.
+    pub fn new(mut machine: IntCode<W>) -> Debugger<W> {
+        let last = String::new();
+        machine.record_recent_history(HISTORY);
+
+        Debugger { machine, last }
+    }
+
+    pub fn machine(&self) -> &IntCode<W> {
+        &self.machine
+    }
+
+    pub fn into_machine(self) -> IntCode<W> {
+        self.machine
+    }
+
+    /// Reads and executes commands from `input` until
+    /// it is exhausted or a quit command is given.
+    pub fn repl<R: BufRead, O: Write>(&mut self, mut input: R, mut out: O) -> io::Result<()> {
+        self.show_current(&mut out)?;
+
+        loop {
+            write!(out, "(intcode) ")?;
+            out.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(out)?;
+                return Ok(());
+            }
+
+            if !self.command(&line, &mut out)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Executes a single command line, returning false if it was quit.
+    pub fn command<O: Write>(&mut self, line: &str, out: &mut O) -> io::Result<bool> {
+        let line = match line.trim() {
+            "" => self.last.clone(),
+            line => line.to_string(),
+        };
+        self.last = line.clone();
+
+        let mut args = line.split_whitespace();
+        let cmd = match args.next() {
+            Some(cmd) => cmd,
+            None => return Ok(true),
+        };
+        let args: Vec<&str> = args.collect();
+
+        let result = match cmd {
+            "s" | "step" => self.step(&args, out),
+            "c" | "continue" => self.cont(out),
+            "rs" | "rstep" => self.rstep(&args, out),
+            "rc" | "rcontinue" => self.rcont(out),
+            "rewind" => self.rewind(&args, out),
+            "b" | "break" => self.set_break(&args, out),
+            "d" | "delete" => self.delete(&args, out),
+            "w" | "watch" => self.watch(&args, out),
+            "unwatch" => self.unwatch(&args, out),
+            "x" | "mem" => self.mem(&args, out),
+            "set" => self.set(&args, out),
+            "in" | "input" => self.input(&args, out),
+            "i" | "info" => self.info(out),
+            "l" | "list" => self.list(&args, out),
+            "h" | "help" => writeln!(out, "{}", HELP).map_err(Failure::Io),
+            "q" | "quit" => return Ok(false),
+            _ => Err(Failure::Usage(format!("unknown command {}, try help", cmd))),
+        };
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(Failure::Usage(message)) => {
+                writeln!(out, "{}", message)?;
+                Ok(true)
+            }
+            Err(Failure::Io(err)) => Err(err),
+        }
+    }
+
+    fn step<O: Write>(&mut self, args: &[&str], out: &mut O) -> Result<(), Failure> {
+        let count = match args {
+            [] => 1,
+            [count] => count
+                .parse()
+                .map_err(|_| Failure::Usage(format!("invalid count {}", count)))?,
+            _ => return usage("step [n]"),
+        };
+
+        for _ in 0..count {
+            match self.machine.step() {
+                Ok(None) => {}
+                Ok(Some(Halt::Output(value))) => writeln!(out, "output {}", value)?,
+                Ok(Some(halt)) => {
+                    report(&halt, out)?;
+                    break;
+                }
+                Err(err) => {
+                    writeln!(out, "error at {}", err)?;
+                    break;
+                }
+            }
+        }
+
+        Ok(self.show_current(out)?)
+    }
+
+    fn cont<O: Write>(&mut self, out: &mut O) -> Result<(), Failure> {
+        loop {
+            match self.machine.exec() {
+                Ok(Halt::Output(value)) => writeln!(out, "output {}", value)?,
+                Ok(halt) => {
+                    report(&halt, out)?;
+                    break;
+                }
+                Err(err) => {
+                    writeln!(out, "error at {}", err)?;
+                    break;
+                }
+            }
+        }
+
+        Ok(self.show_current(out)?)
+    }
+
+    fn rstep<O: Write>(&mut self, args: &[&str], out: &mut O) -> Result<(), Failure> {
+        let count = match args {
+            [] => 1,
+            [count] => count
+                .parse()
+                .map_err(|_| Failure::Usage(format!("invalid count {}", count)))?,
+            _ => return usage("rstep [n]"),
+        };
+
+        for _ in 0..count {
+            if !self.machine.step_back() {
+                writeln!(out, "start of history")?;
+                break;
+            }
+        }
+
+        Ok(self.show_current(out)?)
+    }
+
+    fn rcont<O: Write>(&mut self, out: &mut O) -> Result<(), Failure> {
+        if self.machine.exec_back() {
+            writeln!(out, "breakpoint")?;
+        } else {
+            writeln!(out, "start of history")?;
+        }
+
+        Ok(self.show_current(out)?)
+    }
+
+    fn rewind<O: Write>(&mut self, args: &[&str], out: &mut O) -> Result<(), Failure> {
+        let index = match args {
+            [index] => index
+                .parse()
+                .map_err(|_| Failure::Usage(format!("invalid output {}", index)))?,
+            _ => return usage("rewind n"),
+        };
+
+        if !self.machine.rewind_to_output(index) {
+            writeln!(out, "output {} was not produced", index)?;
+        }
+
+        Ok(self.show_current(out)?)
+    }
+
+    fn set_break<O: Write>(&mut self, args: &[&str], out: &mut O) -> Result<(), Failure> {
+        match args {
+            [] => {
+                for addr in self.machine.breakpoints() {
+                    writeln!(out, "breakpoint at 0x{:04x}", addr)?;
+                }
+            }
+            [addr] => {
+                let addr = parse_addr::<W>(addr)?;
+                self.machine.set_breakpoint(addr);
+                writeln!(out, "breakpoint at 0x{:04x}", addr)?;
+            }
+            _ => return usage("break [addr]"),
+        }
+
+        Ok(())
+    }
+
+    fn delete<O: Write>(&mut self, args: &[&str], out: &mut O) -> Result<(), Failure> {
+        let addr = match args {
+            [addr] => parse_addr::<W>(addr)?,
+            _ => return usage("delete addr"),
+        };
+
+        if !self.machine.clear_breakpoint(addr) {
+            writeln!(out, "no breakpoint at 0x{:04x}", addr)?;
+        }
+
+        Ok(())
+    }
+
+    fn watch<O: Write>(&mut self, args: &[&str], out: &mut O) -> Result<(), Failure> {
+        let (addr, watch) = match args {
+            [] => {
+                for (addr, watch) in self.machine.watchpoints() {
+                    writeln!(out, "watchpoint at 0x{:04x} ({:?})", addr, watch)?;
+                }
+
+                return Ok(());
+            }
+            ["code"] => {
+                self.machine.watch_code(true);
+                writeln!(out, "watching executed code")?;
+
+                return Ok(());
+            }
+            [addr] => (parse_addr::<W>(addr)?, Watch::ReadWrite),
+            [addr, "r"] => (parse_addr::<W>(addr)?, Watch::Read),
+            [addr, "w"] => (parse_addr::<W>(addr)?, Watch::Write),
+            [addr, "rw"] => (parse_addr::<W>(addr)?, Watch::ReadWrite),
+            _ => return usage("watch [addr [r|w|rw]] | watch code"),
+        };
+
+        self.machine.watch(addr, watch);
+        writeln!(out, "watchpoint at 0x{:04x} ({:?})", addr, watch)?;
+
+        Ok(())
+    }
+
+    fn unwatch<O: Write>(&mut self, args: &[&str], out: &mut O) -> Result<(), Failure> {
+        let addr = match args {
+            [addr] => parse_addr::<W>(addr)?,
+            _ => return usage("unwatch addr"),
+        };
+
+        if !self.machine.unwatch(addr) {
+            writeln!(out, "no watchpoint at 0x{:04x}", addr)?;
+        }
+
+        Ok(())
+    }
+
+    fn mem<O: Write>(&mut self, args: &[&str], out: &mut O) -> Result<(), Failure> {
+        let (addr, count) = match args {
+            [addr] => (parse_addr::<W>(addr)?, 1),
+            [addr, count] => (parse_addr::<W>(addr)?, parse_addr::<W>(count)?),
+            _ => return usage("mem addr [n]"),
+        };
+
+        for addr in (addr..=usize::MAX).take(limit(count, out)?) {
+            writeln!(out, "%0x{:04x}: {}", addr, self.machine[addr])?;
+        }
+
+        Ok(())
+    }
+
+    fn set<O: Write>(&mut self, args: &[&str], out: &mut O) -> Result<(), Failure> {
+        let (addr, value) = match args {
+            [addr, value] => (parse_addr::<W>(addr)?, parse_value::<W>(value)?),
+            _ => return usage("set addr value"),
+        };
+
+        writeln!(out, "%0x{:04x}: {} -> {}", addr, self.machine[addr], value)?;
+        self.machine[addr] = value;
+
+        Ok(())
+    }
+
+    fn input<O: Write>(&mut self, args: &[&str], out: &mut O) -> Result<(), Failure> {
+        if args.is_empty() {
+            return usage("input value...");
+        }
+
+        let values = args
+            .iter()
+            .map(|arg| parse_value::<W>(arg))
+            .collect::<Result<Vec<W>, Failure>>()?;
+
+        for value in values {
+            self.machine.push_input(value);
+        }
+
+        self.info(out)
+    }
+
+    fn info<O: Write>(&mut self, out: &mut O) -> Result<(), Failure> {
+        let input: Vec<String> = self
+            .machine
+            .pending_input()
+            .map(|value| value.to_string())
+            .collect();
+
+        writeln!(out, "ip     0x{:04x}", self.machine.ip())?;
+        writeln!(out, "rb     {}", self.machine.relative_base())?;
+        writeln!(out, "halted {}", self.machine.halted())?;
+        writeln!(out, "input  [{}]", input.join(", "))?;
+
+        Ok(())
+    }
+
+    fn list<O: Write>(&mut self, args: &[&str], out: &mut O) -> Result<(), Failure> {
+        let (mut addr, count) = match args {
+            [] => (self.machine.ip(), 8),
+            [addr] => (parse_addr::<W>(addr)?, 8),
+            [addr, count] => (parse_addr::<W>(addr)?, parse_addr::<W>(count)?),
+            _ => return usage("list [addr] [n]"),
+        };
+
+        for _ in 0..limit(count, out)? {
+            let size = match OpCode::decode(self.machine.memory(), addr) {
+                Ok(op) => {
+                    writeln!(out, "0x{:04x}: {}", addr, op)?;
+                    op.size()
+                }
+                Err(_) => {
+                    writeln!(out, "0x{:04x}: .data {}", addr, self.machine[addr])?;
+                    1
+                }
+            };
+
+            match addr.checked_add(size) {
+                Some(next) => addr = next,
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shows the next instruction along with the operand values it would use.
+    fn show_current<O: Write>(&self, out: &mut O) -> io::Result<()> {
+        let ip = self.machine.ip();
+
+        if self.machine.halted() {
+            return writeln!(out, "halted");
+        }
+
+        match OpCode::decode(self.machine.memory(), ip) {
+            Ok(OpCode::Quit) => writeln!(out, "0x{:04x}: {}", ip, OpCode::<W>::Quit),
+            Ok(op) => {
+                let real = op.real(&self.machine);
+                writeln!(out, "0x{:04x}: {:<24} ; {}", ip, op.to_string(), real)
+            }
+            Err(fault) => {
+                let err = fault.at(ip, self.machine[ip].clone());
+                writeln!(out, "0x{:04x}: {}", ip, err)
+            }
+        }
+    }
+}
+
+/// Why a command could not be carried out.
+enum Failure {
+    /// The command was malformed, reported back to the user.
+    Usage(String),
+
+    /// Writing the response failed.
+    Io(io::Error),
+}
+
+impl From<io::Error> for Failure {
+    fn from(err: io::Error) -> Failure {
+        Failure::Io(err)
+    }
+}
+
+fn usage(text: &str) -> Result<(), Failure> {
+    Err(Failure::Usage(format!("usage: {}", text)))
+}
+
+/// `count` capped at `SHOW_LIMIT`, telling the user when it is.
+fn limit<O: Write>(count: usize, out: &mut O) -> io::Result<usize> {
+    if count <= SHOW_LIMIT {
+        return Ok(count);
+    }
+
+    writeln!(out, "showing the first {} of {} lines", SHOW_LIMIT, count)?;
+    Ok(SHOW_LIMIT)
+}
+
+fn report<W: Word, O: Write>(halt: &Halt<W>, out: &mut O) -> io::Result<()> {
+    match halt {
+        Halt::Input => writeln!(out, "waiting for input"),
+        Halt::Output(value) => writeln!(out, "output {}", value),
+        Halt::Quit => writeln!(out, "quit"),
+        Halt::Budget => writeln!(out, "budget exhausted"),
+        Halt::Loop => writeln!(out, "infinite loop detected"),
+        Halt::Breakpoint => writeln!(out, "breakpoint"),
+        Halt::Watch(hit) => writeln!(out, "watchpoint {}", hit),
+    }
+}
+
+fn parse_value<W: Word>(arg: &str) -> Result<W, Failure> {
+    match parse_number(arg) {
+        Some(value) => Ok(value),
+        None => Err(Failure::Usage(format!("invalid value {}", arg))),
+    }
+}
+
+fn parse_addr<W: Word>(arg: &str) -> Result<usize, Failure> {
+    match parse_number::<W>(arg).and_then(|value| value.to_addr()) {
+        Some(addr) => Ok(addr),
+        None => Err(Failure::Usage(format!("invalid address {}", arg))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn session(prog: Vec<i64>, commands: &str) -> (String, IntCode<i64>) {
+        let mut debugger = Debugger::new(IntCode::new(prog, vec![]));
+        let mut out = Vec::new();
+
+        debugger.repl(commands.as_bytes(), &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        (out.replace("(intcode) ", ""), debugger.into_machine())
+    }
+
+    #[test]
+    fn step_and_inspect() {
+        let prog = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let (out, machine) = session(prog, "s\nx 3\nset 0xb 2\n\ns\nq\n");
+
+        assert_eq!(
+            out,
+            "0x0000: ADD  %0x0009, %0x000a, %0x0003 ; 30 + 40 -> %0x0003\n\
+             0x0004: MULT %0x0003, %0x000b, %0x0000 ; 70 * 50 -> %0x0000\n\
+             %0x0003: 70\n\
+             %0x000b: 50 -> 2\n\
+             %0x000b: 2 -> 2\n\
+             0x0008: QT\n"
+        );
+        assert_eq!(machine[0], 140);
+    }
+
+    #[test]
+    fn breakpoints_and_input() {
+        let prog = vec![3, 0, 4, 0, 3, 0, 4, 0, 99];
+        let (out, machine) = session(prog, "b 4\nc\nin 7 0x10\nc\nc\nc\n");
+
+        assert_eq!(
+            out,
+            "0x0000: IN   %0x0000             ; -> %0x0000\n\
+             breakpoint at 0x0004\n\
+             waiting for input\n\
+             0x0000: IN   %0x0000             ; -> %0x0000\n\
+             ip     0x0000\n\
+             rb     0\n\
+             halted false\n\
+             input  [7, 16]\n\
+             output 7\n\
+             breakpoint\n\
+             0x0004: IN   %0x0000             ; -> %0x0000\n\
+             output 16\n\
+             quit\n\
+             halted\n\
+             quit\n\
+             halted\n\
+             \n"
+        );
+        assert!(machine.halted());
+    }
+
+    #[test]
+    fn watchpoints() {
+        let prog = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let (out, _) = session(prog, "w 0 w\nc\n");
+
+        assert_eq!(
+            out,
+            "0x0000: ADD  %0x0009, %0x000a, %0x0003 ; 30 + 40 -> %0x0003\n\
+             watchpoint at 0x0000 (Write)\n\
+             watchpoint 0x0004: MULT %0x0003, %0x000b, %0x0000 wrote %0x0000: 1 -> 3500\n\
+             0x0008: QT\n\
+             \n"
+        );
+    }
+
+    #[test]
+    fn reverse() {
+        let prog = vec![3, 0, 4, 0, 3, 0, 4, 0, 99];
+        let (out, machine) = session(prog, "in 1 2\nc\nrewind 0\nrs 9\nb 4\nc\nrc\n");
+
+        assert!(out.ends_with(
+            "output 1\n\
+             output 2\n\
+             quit\n\
+             halted\n\
+             0x0002: OUT  %0x0000             ; 1\n\
+             start of history\n\
+             0x0000: IN   %0x0000             ; -> %0x0000\n\
+             breakpoint at 0x0004\n\
+             output 1\n\
+             breakpoint\n\
+             0x0004: IN   %0x0000             ; -> %0x0000\n\
+             start of history\n\
+             0x0000: IN   %0x0000             ; -> %0x0000\n\
+             \n"
+        ));
+        assert_eq!(machine.pending_input().collect::<Vec<_>>(), vec![&1, &2]);
+    }
+
+    #[test]
+    fn bad_commands() {
+        let (out, _) = session(vec![99], "jump\nx\nb -1\n");
+
+        assert_eq!(
+            out,
+            "0x0000: QT\n\
+             unknown command jump, try help\n\
+             usage: mem addr [n]\n\
+             invalid address -1\n\
+             \n"
+        );
+    }
+
+    #[test]
+    fn huge_numbers() {
+        let huge = "0xfffffffffffffffff";
+        let commands = format!(
+            "x {0}\nset {0} 1\nset 0 {0}\nset 0x1000000000000000 1\nx 0x1000000000000000\n",
+            huge
+        );
+        let (out, machine) = session(vec![99], &commands);
+
+        assert_eq!(
+            out,
+            "0x0000: QT\n\
+             invalid address 0xfffffffffffffffff\n\
+             invalid address 0xfffffffffffffffff\n\
+             invalid value 0xfffffffffffffffff\n\
+             %0x1000000000000000: 0 -> 1\n\
+             %0x1000000000000000: 1\n\
+             \n"
+        );
+        assert!(machine.memory().is_sparse());
+    }
+
+    #[test]
+    fn long_listings() {
+        let (out, _) = session(
+            vec![99],
+            "x 0 99999999999
+l 0 0x1000000
+",
+        );
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!(lines[1], "showing the first 1000 of 99999999999 lines");
+        assert_eq!(lines[2], "%0x0000: 99");
+        assert_eq!(lines[1001], "%0x03e7: 0");
+        assert_eq!(lines[1002], "showing the first 1000 of 16777216 lines");
+        assert_eq!(lines[1003], "0x0000: QT");
+        assert_eq!(lines[2002], "0x03e7: .data 0");
+    }
+}
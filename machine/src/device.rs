@@ -0,0 +1,197 @@
+//! Input and output devices an `IntCode` machine can be connected to.
+//!
+//! A `Source` attached with `IntCode::input_from` is consulted whenever an
+//! input instruction finds the pushed input queue empty. A `Sink` attached
+//! with `IntCode::output_to` receives outputs as they are produced instead
+//! of the machine halting with `Halt::Output`.
+
+use crate::Word;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex, PoisonError};
+
+pub trait Source<W>: Send {
+    /// The next input value, or `None` if there is none to give.
+    fn next(&mut self) -> Option<W>;
+}
+
+pub trait Sink<W>: Send {
+    fn send(&mut self, value: W);
+}
+
+impl<W: Word> Source<W> for VecDeque<W> {
+    fn next(&mut self) -> Option<W> {
+        self.pop_front()
+    }
+}
+
+impl<W: Word> Source<W> for Vec<W> {
+    fn next(&mut self) -> Option<W> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.remove(0))
+        }
+    }
+}
+
+/// Collects outputs where a clone of the `Arc` can read them.
+impl<W: Word> Sink<W> for Arc<Mutex<Vec<W>>> {
+    fn send(&mut self, value: W) {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(value);
+    }
+}
+
+impl<W: Word> Sink<W> for Arc<Mutex<VecDeque<W>>> {
+    fn send(&mut self, value: W) {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push_back(value);
+    }
+}
+
+/// Blocks until a value arrives, giving `None` once every sender is gone.
+impl<W: Word> Source<W> for Receiver<W> {
+    fn next(&mut self) -> Option<W> {
+        self.recv().ok()
+    }
+}
+
+/// Outputs sent after the receiver is dropped are discarded.
+impl<W: Word> Sink<W> for Sender<W> {
+    fn send(&mut self, value: W) {
+        let _ = Sender::send(self, value);
+    }
+}
+
+impl<W: Word> Sink<W> for SyncSender<W> {
+    fn send(&mut self, value: W) {
+        let _ = SyncSender::send(self, value);
+    }
+}
+
+/// A source calling a closure for each input, see `source_fn`.
+#[derive(Debug, Clone)]
+pub struct SourceFn<F>(F);
+
+/// A sink calling a closure with each output, see `sink_fn`.
+#[derive(Debug, Clone)]
+pub struct SinkFn<F>(F);
+
+/// Computes each input on demand.
+pub fn source_fn<W, F: FnMut() -> Option<W> + Send>(f: F) -> SourceFn<F> {
+    SourceFn(f)
+}
+
+/// Reacts to each output as it is produced.
+pub fn sink_fn<W, F: FnMut(W) + Send>(f: F) -> SinkFn<F> {
+    SinkFn(f)
+}
+
+impl<W, F: FnMut() -> Option<W> + Send> Source<W> for SourceFn<F> {
+    fn next(&mut self) -> Option<W> {
+        (self.0)()
+    }
+}
+
+impl<W, F: FnMut(W) + Send> Sink<W> for SinkFn<F> {
+    fn send(&mut self, value: W) {
+        (self.0)(value)
+    }
+}
+
+/// Reads one value per line from stdin.
+///
+/// Gives `None` at end of input or on a line that is not a valid word.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stdin;
+
+/// Prints one value per line to stdout.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stdout;
+
+impl<W: Word> Source<W> for Stdin {
+    fn next(&mut self) -> Option<W> {
+        let mut buf = String::new();
+
+        match io::stdin().lock().read_line(&mut buf) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => buf.trim().parse().ok(),
+        }
+    }
+}
+
+impl<W: Word> Sink<W> for Stdout {
+    fn send(&mut self, value: W) {
+        let mut out = io::stdout().lock();
+
+        let _ = writeln!(out, "{}", value);
+        let _ = out.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::DOUBLER;
+    use crate::{Halt, IntCode};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn closures() {
+        let outputs = Arc::new(Mutex::new(Vec::new()));
+        let mut machine = IntCode::new(DOUBLER.to_vec(), vec![]);
+
+        let seen = outputs.clone();
+        machine.input_from(source_fn(move || match seen.lock().unwrap().last() {
+            None => Some(1),
+            Some(last) if *last > 16 => Some(0),
+            Some(last) => Some(last + 1),
+        }));
+
+        let sent = outputs.clone();
+        machine.output_to(sink_fn(move |out| sent.lock().unwrap().push(out)));
+
+        assert_eq!(machine.exec(), Ok(Halt::Quit));
+        assert_eq!(*outputs.lock().unwrap(), vec![2, 6, 14, 30]);
+    }
+
+    #[test]
+    fn channels() {
+        let (in_tx, in_rx) = channel();
+        let (out_tx, out_rx) = channel();
+        let mut machine = IntCode::new(DOUBLER.to_vec(), vec![4]);
+
+        machine.input_from(in_rx);
+        machine.output_to(out_tx);
+
+        in_tx.send(5).unwrap();
+        drop(in_tx);
+
+        assert_eq!(machine.exec(), Ok(Halt::Input));
+        assert_eq!(out_rx.try_iter().collect::<Vec<i64>>(), vec![8, 10]);
+    }
+
+    #[test]
+    fn shared_vec() {
+        let outputs = Arc::new(Mutex::new(Vec::new()));
+        let mut machine = IntCode::new(DOUBLER.to_vec(), vec![3, 4, 0]);
+        machine.output_to(outputs.clone());
+
+        assert_eq!(machine.exec(), Ok(Halt::Quit));
+        assert_eq!(*outputs.lock().unwrap(), vec![6, 8]);
+    }
+
+    #[test]
+    fn pushed_input_first() {
+        let mut machine = IntCode::new(DOUBLER.to_vec(), vec![1]);
+
+        machine.input_from(vec![2, 0]);
+        machine.push_input(3);
+
+        assert_eq!(machine.run(), Ok(vec![2, 6, 4]));
+    }
+}
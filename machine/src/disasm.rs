@@ -0,0 +1,307 @@
+//! Static disassembly of whole program images.
+//!
+//! Instructions are found by following every path from address 0, so
+//! words that are only ever read or written as data are listed as
+//! `.data`. Jump targets with an immediate address are labelled, and the
+//! listing assembles back into the exact same image with `asm::assemble`.
+//!
+//! ```text
+//!         IN   %0x0064
+//! L0002:  JF   %0x0064, L000e
+//!         MULT %0x0064, 0x0002, %0x0064
+//!         OUT  %0x0064
+//!         JT   0x0001, L0002
+//! L000e:  QT
+//!         .data 0
+//! ```
+
+use crate::{Memory, OpCode, Param, Word};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// The most words put on a single `.data` line.
+const DATA_WIDTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item<W> {
+    Instr(OpCode<W>),
+    Data(Vec<W>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line<W> {
+    pub addr: usize,
+    pub label: Option<String>,
+    pub item: Item<W>,
+}
+
+/// A disassembled program, in address order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Listing<W> {
+    pub lines: Vec<Line<W>>,
+}
+
+/// Disassembles a program image starting execution at address 0.
+pub fn disassemble<W: Word>(image: &[W]) -> Listing<W> {
+    disassemble_from(image, &[0])
+}
+
+/// Disassembles a program image following every path from `entries`,
+/// e.g. the current ip of a running machine alongside address 0.
+pub fn disassemble_from<W: Word>(image: &[W], entries: &[usize]) -> Listing<W> {
+    let mem = Memory::dense(image.to_vec());
+    let mut claimed = vec![false; image.len()];
+    let mut instrs = BTreeMap::new();
+    let mut targets = BTreeSet::new();
+    let mut pending: Vec<usize> = entries.iter().rev().copied().collect();
+
+    while let Some(addr) = pending.pop() {
+        if addr >= image.len() || claimed[addr] {
+            continue;
+        }
+
+        let op = match OpCode::decode(&mem, addr) {
+            Ok(op) => op,
+            Err(_) => continue,
+        };
+
+        // only accept words the assembler would produce, so that
+        // the listing reassembles into the same image
+        let end = addr + op.size();
+        if image[addr].to_code() != Some(op.code())
+            || end > image.len()
+            || claimed[addr..end].iter().any(|claimed| *claimed)
+        {
+            continue;
+        }
+
+        claimed[addr..end].iter_mut().for_each(|cell| *cell = true);
+
+        let (jump, falls) = flow(&op);
+        if let Some(target) = jump {
+            targets.insert(target);
+            pending.push(target);
+        }
+        if falls {
+            pending.push(end);
+        }
+
+        instrs.insert(addr, op);
+    }
+
+    let label = |addr: usize| {
+        if instrs.contains_key(&addr) && targets.contains(&addr) {
+            Some(format!("L{:04x}", addr))
+        } else {
+            None
+        }
+    };
+
+    let mut lines = Vec::new();
+    let mut addr = 0;
+
+    while addr < image.len() {
+        if let Some(op) = instrs.get(&addr) {
+            lines.push(Line {
+                addr,
+                label: label(addr),
+                item: Item::Instr(op.clone()),
+            });
+
+            addr += op.size();
+        } else {
+            let data: Vec<W> = image[addr..]
+                .iter()
+                .take(DATA_WIDTH)
+                .enumerate()
+                .take_while(|(i, _)| !instrs.contains_key(&(addr + i)))
+                .map(|(_, word)| word.clone())
+                .collect();
+
+            let len = data.len();
+            lines.push(Line {
+                addr,
+                label: None,
+                item: Item::Data(data),
+            });
+
+            addr += len;
+        }
+    }
+
+    Listing { lines }
+}
+
+/// Where control can go after `op`: an immediate jump target,
+/// and whether execution can fall through to the next instruction.
+fn flow<W: Word>(op: &OpCode<W>) -> (Option<usize>, bool) {
+    let zero = W::from(0);
+
+    let (cond, target, jumps_on) = match op {
+        OpCode::Quit => return (None, false),
+        OpCode::JumpTrue(cond, target) => (cond, target, true),
+        OpCode::JumpFalse(cond, target) => (cond, target, false),
+        _ => return (None, true),
+    };
+
+    let (jumps, falls) = match cond {
+        Param::Inter(value) => {
+            let taken = (*value != zero) == jumps_on;
+            (taken, !taken)
+        }
+        _ => (true, true),
+    };
+
+    let target = match target {
+        Param::Inter(target) if jumps => target.to_addr(),
+        _ => None,
+    };
+
+    (target, falls)
+}
+
+impl<W: Word> Listing<W> {
+    /// The label of the instruction at `addr`, if it has one.
+    pub fn label(&self, addr: usize) -> Option<&str> {
+        self.lines
+            .iter()
+            .find(|line| line.addr == addr)
+            .and_then(|line| line.label.as_deref())
+    }
+
+    /// A parameter of `op`, with immediate jump targets replaced by their label.
+    fn operand(&self, op: &OpCode<W>, index: usize, param: &Param<W>) -> String {
+        let is_target = index == 1 && matches!(op, OpCode::JumpTrue(..) | OpCode::JumpFalse(..));
+
+        let label = match param {
+            Param::Inter(target) if is_target => target.to_addr().and_then(|addr| self.label(addr)),
+            _ => None,
+        };
+
+        match label {
+            Some(label) => label.to_string(),
+            None => param.to_string(),
+        }
+    }
+}
+
+impl<W: Word> Display for Listing<W> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        for line in &self.lines {
+            let label = match &line.label {
+                Some(label) => format!("{}:", label),
+                None => String::new(),
+            };
+
+            match &line.item {
+                Item::Instr(op) => {
+                    let params: Vec<String> = op
+                        .params()
+                        .iter()
+                        .enumerate()
+                        .map(|(i, param)| self.operand(op, i, param))
+                        .collect();
+
+                    if params.is_empty() {
+                        writeln!(fmt, "{:<8}{}", label, op.mnemonic())?;
+                    } else {
+                        writeln!(
+                            fmt,
+                            "{:<8}{:<4} {}",
+                            label,
+                            op.mnemonic(),
+                            params.join(", ")
+                        )?;
+                    }
+                }
+
+                Item::Data(words) => {
+                    let words: Vec<String> = words.iter().map(W::to_string).collect();
+                    writeln!(fmt, "{:<8}.data {}", label, words.join(", "))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::fixtures::FEEDBACK;
+
+    fn round_trip(prog: Vec<i64>) -> String {
+        let text = disassemble(&prog).to_string();
+
+        assert_eq!(assemble::<i64>(&text).unwrap(), prog, "{}", text);
+        text
+    }
+
+    #[test]
+    fn labels() {
+        let prog = vec![
+            3, 100, 1006, 100, 14, 1002, 100, 2, 100, 4, 100, 1105, 1, 2, 99,
+        ];
+
+        assert_eq!(
+            round_trip(prog),
+            "        IN   %0x0064\n\
+             L0002:  JF   %0x0064, L000e\n\
+             \x20       MULT %0x0064, 0x0002, %0x0064\n\
+             \x20       OUT  %0x0064\n\
+             \x20       JT   0x0001, L0002\n\
+             L000e:  QT\n"
+        );
+    }
+
+    #[test]
+    fn trailing_data() {
+        let text = round_trip(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+
+        assert!(text.ends_with("        QT\n        .data 30, 40, 50\n"));
+    }
+
+    #[test]
+    fn data_between_code() {
+        // jumps over the table it prints from
+        let prog = vec![1105, 1, 7, 5, 6, 7, 10099, 4, 3, 4, 4, 99];
+        let listing = disassemble(&prog);
+
+        let items: Vec<(usize, bool)> = listing
+            .lines
+            .iter()
+            .map(|line| (line.addr, matches!(line.item, Item::Instr(_))))
+            .collect();
+
+        assert_eq!(
+            items,
+            vec![(0, true), (3, false), (7, true), (9, true), (11, true)]
+        );
+        assert_eq!(listing.label(7), Some("L0007"));
+        round_trip(prog);
+    }
+
+    #[test]
+    fn extra_entries() {
+        // the jump target is only known at run time
+        let prog = vec![105, 1, 3, 6, 99, 0, 4, 5, 99];
+        let addrs = |listing: Listing<i64>| -> Vec<usize> {
+            listing.lines.iter().map(|line| line.addr).collect()
+        };
+
+        assert_eq!(addrs(disassemble(&prog)), vec![0, 3]);
+        assert_eq!(addrs(disassemble_from(&prog, &[0, 6])), vec![0, 3, 6, 8]);
+    }
+
+    #[test]
+    fn programs() {
+        round_trip(FEEDBACK.to_vec());
+
+        let quine = round_trip(vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ]);
+        assert!(quine.starts_with("L0000:  ARB  0x0001\n"));
+    }
+}
@@ -0,0 +1,100 @@
+use crate::word::{Hex, Word};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// A fault raised while executing an instruction.
+///
+/// Every variant carries the address of the faulting instruction
+/// and its raw, undecoded value.
+#[derive(Debug, PartialEq, Clone)]
+pub enum MachineError<W = i64> {
+    /// The instruction's opcode is not one the machine knows.
+    UnknownOpcode { ip: usize, instr: W },
+
+    /// A parameter uses a mode other than positional, immediate or relative.
+    InvalidMode { ip: usize, instr: W, mode: i64 },
+
+    /// The instruction tried to write through an immediate parameter.
+    ImmediateWrite { ip: usize, instr: W },
+
+    /// An operand resolved to a negative or unrepresentable address.
+    InvalidAddress { ip: usize, instr: W, addr: W },
+
+    /// An addition or multiplication overflowed the word.
+    Overflow { ip: usize, instr: W },
+
+    /// The instruction extends past the last address.
+    EndOfMemory { ip: usize, instr: W },
+}
+
+/// A fault before it is attributed to an instruction.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum Fault<W> {
+    UnknownOpcode,
+    InvalidMode(i64),
+    ImmediateWrite,
+    InvalidAddress(W),
+    Overflow,
+    EndOfMemory,
+}
+
+impl<W> Fault<W> {
+    pub(crate) fn at(self, ip: usize, instr: W) -> MachineError<W> {
+        match self {
+            Fault::UnknownOpcode => MachineError::UnknownOpcode { ip, instr },
+            Fault::InvalidMode(mode) => MachineError::InvalidMode { ip, instr, mode },
+            Fault::ImmediateWrite => MachineError::ImmediateWrite { ip, instr },
+            Fault::InvalidAddress(addr) => MachineError::InvalidAddress { ip, instr, addr },
+            Fault::Overflow => MachineError::Overflow { ip, instr },
+            Fault::EndOfMemory => MachineError::EndOfMemory { ip, instr },
+        }
+    }
+}
+
+impl<W> MachineError<W> {
+    /// Address of the faulting instruction.
+    pub fn ip(&self) -> usize {
+        match self {
+            MachineError::UnknownOpcode { ip, .. } => *ip,
+            MachineError::InvalidMode { ip, .. } => *ip,
+            MachineError::ImmediateWrite { ip, .. } => *ip,
+            MachineError::InvalidAddress { ip, .. } => *ip,
+            MachineError::Overflow { ip, .. } => *ip,
+            MachineError::EndOfMemory { ip, .. } => *ip,
+        }
+    }
+
+    /// Raw value of the faulting instruction.
+    pub fn instr(&self) -> &W {
+        match self {
+            MachineError::UnknownOpcode { instr, .. } => instr,
+            MachineError::InvalidMode { instr, .. } => instr,
+            MachineError::ImmediateWrite { instr, .. } => instr,
+            MachineError::InvalidAddress { instr, .. } => instr,
+            MachineError::Overflow { instr, .. } => instr,
+            MachineError::EndOfMemory { instr, .. } => instr,
+        }
+    }
+}
+
+impl<W: Word> Display for MachineError<W> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        write!(fmt, "0x{:04x} ({}): ", self.ip(), self.instr())?;
+
+        match self {
+            MachineError::UnknownOpcode { .. } => write!(fmt, "unrecognized opcode"),
+            MachineError::InvalidMode { mode, .. } => {
+                write!(fmt, "invalid argument mode: {}", mode)
+            }
+            MachineError::ImmediateWrite { .. } => write!(fmt, "can not set in intermediate mode"),
+            MachineError::InvalidAddress { addr, .. } => {
+                write!(fmt, "invalid address: {}", Hex(addr))
+            }
+            MachineError::Overflow { .. } => write!(fmt, "arithmetic overflow"),
+            MachineError::EndOfMemory { .. } => {
+                write!(fmt, "instruction runs past the end of memory")
+            }
+        }
+    }
+}
+
+impl<W: Word> std::error::Error for MachineError<W> {}
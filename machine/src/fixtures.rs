@@ -0,0 +1,16 @@
+//! Programs shared by tests across modules.
+
+/// Doubles each input until it reads a zero.
+pub(crate) const DOUBLER: [i64; 15] = [
+    3, 100, 1006, 100, 14, 1002, 100, 2, 100, 4, 100, 1105, 1, 0, 99,
+];
+
+/// Echoes each input forever.
+pub(crate) const ECHO: [i64; 8] = [3, 7, 4, 7, 1105, 1, 0, 0];
+
+/// The feedback amplifier from day 7's example. It reads its phase, then
+/// outputs a signal for each signal it reads, keeping the last at 27.
+pub(crate) const FEEDBACK: [i64; 29] = [
+    3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28, 1005,
+    28, 6, 99, 0, 0, 5,
+];
@@ -0,0 +1,115 @@
+//! Reading and writing the line oriented text snapshots and coverage are
+//! saved as: a header line, then one `key value` line per field.
+
+use crate::Word;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub enum FormatError {
+    /// Line `line` (1 based) is missing or malformed.
+    Format { line: usize, message: String },
+
+    /// The file could not be read.
+    Io(io::Error),
+}
+
+pub(crate) fn save<T: Display, P: AsRef<Path>>(value: &T, path: P) -> io::Result<()> {
+    std::fs::write(path, value.to_string())
+}
+
+pub(crate) fn load<T, P>(path: P) -> Result<T, FormatError>
+where
+    T: FromStr<Err = FormatError>,
+    P: AsRef<Path>,
+{
+    std::fs::read_to_string(path)
+        .map_err(FormatError::Io)?
+        .parse()
+}
+
+/// Comma separated `items` as written in a field.
+pub(crate) fn join<T: Display>(items: impl IntoIterator<Item = T>) -> String {
+    let items: Vec<String> = items.into_iter().map(|item| item.to_string()).collect();
+
+    items.join(",")
+}
+
+/// Source text read field by field, keeping track of the line for errors.
+pub(crate) struct Lines<'a> {
+    lines: std::str::Lines<'a>,
+    line: usize,
+}
+
+impl<'a> Lines<'a> {
+    pub(crate) fn new(src: &'a str) -> Lines<'a> {
+        Lines {
+            lines: src.lines(),
+            line: 0,
+        }
+    }
+
+    pub(crate) fn err<T>(&self, message: String) -> Result<T, FormatError> {
+        let line = self.line;
+
+        Err(FormatError::Format { line, message })
+    }
+
+    pub(crate) fn invalid<T>(&self, token: &str) -> Result<T, FormatError> {
+        self.err(format!("invalid value {:?}", token))
+    }
+
+    /// Checks the next line is exactly `header`.
+    pub(crate) fn header(&mut self, header: &str) -> Result<(), FormatError> {
+        if !self.field(header)?.is_empty() {
+            return self.err(format!("expected {}", header));
+        }
+
+        Ok(())
+    }
+
+    /// The rest of the next line after `key` and a space.
+    pub(crate) fn field(&mut self, key: &str) -> Result<&'a str, FormatError> {
+        self.line += 1;
+
+        let line = match self.lines.next() {
+            Some(line) => line,
+            None => return self.err(format!("expected {}", key)),
+        };
+
+        match line.strip_prefix(key) {
+            Some(rest) if rest.is_empty() => Ok(rest),
+            Some(rest) if rest.starts_with(' ') => Ok(&rest[1..]),
+            _ => self.err(format!("expected {}", key)),
+        }
+    }
+
+    pub(crate) fn parse<T: FromStr>(&self, token: &str) -> Result<T, FormatError> {
+        match token.parse() {
+            Ok(value) => Ok(value),
+            Err(_) => self.invalid(token),
+        }
+    }
+
+    /// The comma separated tokens of a field, none if it is empty.
+    pub(crate) fn tokens(list: &'a str) -> impl Iterator<Item = &'a str> {
+        list.split(',').filter(|token| !token.is_empty())
+    }
+
+    pub(crate) fn words<W: Word>(&self, list: &str) -> Result<Vec<W>, FormatError> {
+        Lines::tokens(list).map(|token| self.parse(token)).collect()
+    }
+}
+
+impl Display for FormatError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            FormatError::Format { line, message } => write!(fmt, "line {}: {}", line, message),
+            FormatError::Io(err) => write!(fmt, "unable to read file: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
@@ -0,0 +1,207 @@
+use crate::Word;
+use std::collections::VecDeque;
+
+/// What an executed instruction changed, enough to undo it.
+#[derive(Debug, Clone)]
+pub(crate) struct Entry<W> {
+    pub(crate) ip: usize,
+    pub(crate) rb: W,
+
+    /// Cells written, as (address, previous value), in write order.
+    pub(crate) writes: Vec<(usize, W)>,
+
+    /// Input the instruction consumed.
+    pub(crate) input: Option<W>,
+
+    /// Whether the instruction produced an output.
+    pub(crate) output: bool,
+}
+
+/// Undo log of executed instructions, oldest first.
+#[derive(Debug, Clone)]
+pub(crate) struct History<W> {
+    entries: VecDeque<Entry<W>>,
+
+    /// Entries kept before the oldest are dropped, if limited.
+    limit: Option<usize>,
+
+    /// The instruction being executed.
+    current: Option<Entry<W>>,
+
+    /// Outputs produced since logging started, including dropped ones.
+    outputs: usize,
+
+    /// Outputs produced by dropped entries.
+    dropped: usize,
+}
+
+impl<W: Word> History<W> {
+    pub(crate) fn new(limit: Option<usize>) -> History<W> {
+        History {
+            entries: VecDeque::new(),
+            limit,
+            current: None,
+            outputs: 0,
+            dropped: 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn outputs(&self) -> usize {
+        self.outputs
+    }
+
+    /// Index of the oldest output still logged.
+    pub(crate) fn first_output(&self) -> usize {
+        self.dropped
+    }
+
+    /// Starts logging the instruction at `ip`.
+    pub(crate) fn begin(&mut self, ip: usize, rb: &W) {
+        self.current = Some(Entry {
+            ip,
+            rb: rb.clone(),
+            writes: Vec::new(),
+            input: None,
+            output: false,
+        });
+    }
+
+    pub(crate) fn write(&mut self, addr: usize, old: &W) {
+        if let Some(entry) = &mut self.current {
+            entry.writes.push((addr, old.clone()));
+        }
+    }
+
+    pub(crate) fn input(&mut self, value: &W) {
+        if let Some(entry) = &mut self.current {
+            entry.input = Some(value.clone());
+        }
+    }
+
+    /// Logs the current instruction as executed.
+    pub(crate) fn commit(&mut self, output: bool) {
+        if let Some(mut entry) = self.current.take() {
+            entry.output = output;
+            self.outputs += output as usize;
+            self.entries.push_back(entry);
+        }
+
+        if self.limit.is_some_and(|limit| self.entries.len() > limit) {
+            if let Some(oldest) = self.entries.pop_front() {
+                self.dropped += oldest.output as usize;
+            }
+        }
+    }
+
+    /// Drops the current instruction, it did not execute.
+    pub(crate) fn abort(&mut self) {
+        self.current = None;
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Entry<W>> {
+        let entry = self.entries.pop_back()?;
+        self.outputs -= entry.output as usize;
+
+        Some(entry)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self = History::new(self.limit);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Halt, IntCode};
+
+    // adds pairs of inputs until it reads a zero
+    const ADDER: [i64; 19] = [
+        3, 17, 1006, 17, 16, 3, 18, 1, 17, 18, 18, 4, 18, 1105, 1, 0, 99, 0, 0,
+    ];
+
+    #[test]
+    fn step_back() {
+        let mut machine = IntCode::new(ADDER.to_vec(), vec![2, 3]);
+        machine.record_history(true);
+
+        assert_eq!(machine.exec(), Ok(Halt::Output(5)));
+        assert_eq!(machine.history_len(), 5);
+
+        assert!(machine.step_back());
+        assert_eq!(machine.ip(), 11);
+        assert!(machine.step_back());
+        assert_eq!(machine[18], 3);
+        assert!(machine.step_back());
+        assert_eq!(machine.pending_input().collect::<Vec<_>>(), vec![&3]);
+
+        assert_eq!(machine.exec(), Ok(Halt::Output(5)));
+    }
+
+    #[test]
+    fn rewind_to_output() {
+        let mut machine = IntCode::new(ADDER.to_vec(), vec![1, 2, 30, 40, 0]);
+        machine.record_history(true);
+
+        assert_eq!(machine.run(), Ok(vec![3, 70]));
+        assert!(machine.halted());
+
+        assert!(machine.rewind_to_output(0));
+        assert_eq!(machine.ip(), 11);
+        assert_eq!(machine[18], 3);
+        assert_eq!(
+            machine.pending_input().collect::<Vec<_>>(),
+            vec![&30, &40, &0]
+        );
+        assert!(!machine.rewind_to_output(1));
+
+        assert_eq!(machine.run(), Ok(vec![3, 70]));
+    }
+
+    #[test]
+    fn limited() {
+        let mut machine = IntCode::new(ADDER.to_vec(), vec![1, 2, 30, 40, 0]);
+        machine.record_recent_history(6);
+
+        assert_eq!(machine.run(), Ok(vec![3, 70]));
+        assert_eq!(machine.history_len(), 6);
+
+        // the first output was dropped with the oldest instructions
+        assert!(!machine.rewind_to_output(0));
+        assert!(machine.rewind_to_output(1));
+        assert_eq!(machine.ip(), 11);
+        assert_eq!(machine.history_len(), 1);
+
+        assert!(machine.step_back());
+        assert!(!machine.step_back());
+        assert_eq!(machine.ip(), 7);
+    }
+
+    #[test]
+    fn exec_back_to_breakpoint() {
+        let mut machine = IntCode::new(ADDER.to_vec(), vec![1, 2, 30, 40, 0]);
+        machine.record_history(true);
+        machine.run().unwrap();
+
+        machine.set_breakpoint(5);
+        assert!(machine.exec_back());
+        assert_eq!(machine.ip(), 5);
+        assert_eq!(machine.pending_input().collect::<Vec<_>>(), vec![&40, &0]);
+
+        assert!(machine.exec_back());
+        assert_eq!(
+            machine.pending_input().collect::<Vec<_>>(),
+            vec![&2, &30, &40, &0]
+        );
+
+        // resuming runs past the breakpoint it stopped on
+        assert_eq!(machine.exec(), Ok(Halt::Output(3)));
+
+        machine.clear_breakpoint(5);
+        assert!(!machine.exec_back());
+        assert_eq!(machine.ip(), 0);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::cache::{self, Table};
+use crate::{Memory, Program, Word};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::ops::Deref;
+use std::sync::{Arc, OnceLock};
+
+/// An immutable program image shared by the machines loaded from it.
+///
+/// Cloning an image or loading a machine from one copies no words;
+/// each machine copies only the pages it writes, see `Memory::shared`.
+/// The image's code is decoded once, for the instruction caches of all
+/// machines loaded from it.
+#[derive(Clone)]
+pub struct Image<W = i64> {
+    words: Arc<[W]>,
+    code: Arc<OnceLock<Arc<Table<W>>>>,
+}
+
+impl<W: Word> Image<W> {
+    /// Instructions reachable from address 0, decoded on first use.
+    pub(crate) fn code(&self) -> Arc<Table<W>> {
+        self.code
+            .get_or_init(|| Arc::new(cache::decode_code(&self.words)))
+            .clone()
+    }
+}
+
+impl<W: Debug> Debug for Image<W> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        fmt.debug_tuple("Image").field(&self.words).finish()
+    }
+}
+
+impl<W: PartialEq> PartialEq for Image<W> {
+    fn eq(&self, other: &Image<W>) -> bool {
+        self.words == other.words
+    }
+}
+
+impl<W> Deref for Image<W> {
+    type Target = [W];
+
+    fn deref(&self) -> &[W] {
+        &self.words
+    }
+}
+
+impl<W> From<Vec<W>> for Image<W> {
+    fn from(words: Vec<W>) -> Image<W> {
+        Image {
+            words: words.into(),
+            code: Arc::default(),
+        }
+    }
+}
+
+impl<W: Word> From<Program<W>> for Image<W> {
+    fn from(program: Program<W>) -> Image<W> {
+        Image::from(program.into_vec())
+    }
+}
+
+impl<W: Word> From<&Image<W>> for Memory<W> {
+    fn from(image: &Image<W>) -> Memory<W> {
+        Memory::shared(image)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::IntCode;
+
+    #[test]
+    fn machines_share_image() {
+        let image = Image::from(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+
+        let mut a: IntCode<i64> = IntCode::new(&image, vec![]);
+        let mut b: IntCode<i64> = IntCode::new(&image, vec![]);
+        b[9] = 1;
+
+        a.run().unwrap();
+        b.run().unwrap();
+
+        assert_eq!(a[0], 3500);
+        assert_eq!(b[0], 2050);
+        assert_eq!(image[0], 1);
+        assert_eq!(a.memory().copied_pages(), 1);
+    }
+}
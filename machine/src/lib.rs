@@ -1,290 +1,1129 @@
+use std::collections::{BTreeSet, VecDeque};
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
-#[derive(Debug)]
-pub struct IntCode {
-    space: Vec<i32>,
-    input: Vec<i32>,
+pub mod asm;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+mod cache;
+mod coverage;
+pub mod debug;
+pub mod device;
+pub mod disasm;
+mod error;
+#[cfg(test)]
+pub(crate) mod fixtures;
+mod format;
+mod history;
+mod image;
+mod memory;
+pub mod net;
+mod profile;
+mod program;
+pub mod sched;
+mod snapshot;
+pub mod supervisor;
+pub mod trace;
+mod watch;
+mod watchdog;
+mod word;
+
+pub use coverage::{Coverage, Status, Summary};
+pub use error::MachineError;
+pub use format::FormatError;
+pub use image::Image;
+pub use memory::Memory;
+pub use profile::Profile;
+pub use program::{ParseError, Program};
+pub use snapshot::Snapshot;
+pub use watch::{Access, Watch, Watchpoint};
+pub use word::{Hex, Word};
+
+use cache::Cache;
+use device::{Sink, Source};
+use error::Fault;
+use history::History;
+use trace::{Record, Tracer};
+use watch::Watcher;
+use watchdog::LoopDetector;
+
+pub struct IntCode<W = i64> {
+    mem: Memory<W>,
+    cache: Cache<W>,
+    input: VecDeque<W>,
     on: bool,
-    ip: i32,
+    ip: usize,
+    rb: W,
+
+    /// Yielded a fault as an iterator, so iteration has ended.
+    faulted: bool,
+
+    source: Option<Box<dyn Source<W>>>,
+    sink: Option<Box<dyn Sink<W>>>,
+
+    budget: Option<u64>,
+    loops: Option<LoopDetector<W>>,
+
+    breakpoints: BTreeSet<usize>,
+    /// Stopped on the breakpoint at ip, so the next `exec` runs past it.
+    on_break: bool,
+
+    watch: Watcher<W>,
+    /// A watched access not reported yet because its instruction halted.
+    hit: Option<Watchpoint<W>>,
+
+    tracer: Option<Box<dyn Tracer<W>>>,
+    writes: Vec<(usize, W)>,
+
+    history: Option<History<W>>,
+    profile: Option<Profile>,
+    coverage: Option<Coverage>,
+}
+
+/// Reason the machine stopped executing instructions.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Halt<W = i64> {
+    /// An input instruction is waiting on an empty input queue
+    /// and the attached input source, if any, had nothing to give.
+    /// The instruction has not been executed and will be retried on resume.
+    Input,
+
+    /// An output instruction produced a value
+    /// and no output sink is attached.
+    Output(W),
+
+    /// The machine executed a quit instruction.
+    Quit,
+
+    /// The instruction budget ran out before the next instruction.
+    Budget,
+
+    /// The machine is about to repeat a state it was in earlier
+    /// without consuming input since, so it would never halt.
+    Loop,
+
+    /// The next instruction is at a breakpoint and has not been executed.
+    /// Resuming executes it rather than stopping again.
+    Breakpoint,
+
+    /// An instruction made a watched access, or wrote into code that has
+    /// already executed. The instruction has been executed.
+    Watch(Watchpoint<W>),
+}
+
+/// Where execution continues after an instruction.
+enum Flow<W> {
+    /// The following instruction.
+    Next,
+
+    /// The instruction at the jump target.
+    Jump(usize),
+
+    /// Stop. Input is retried on resume, anything else
+    /// resumes at the following instruction.
+    Halt(Halt<W>),
 }
 
 macro_rules! param_arg {
-    ($machine: expr, $code: expr, $offset: literal) => {
+    ($mem: expr, $ip: expr, $code: expr, $offset: literal) => {
         Param::digit(
-            $machine[$machine.ip + 1 + $offset],
+            $mem.get($ip.checked_add(1 + $offset).ok_or(Fault::EndOfMemory)?)
+                .clone(),
             dec_digit($code, 2 + $offset),
-        )
+        )?
     };
 }
 
 #[inline]
-fn dec_digit(base: i32, digit: u32) -> i32 {
+fn dec_digit(base: i64, digit: u32) -> i64 {
     let mut r = base;
-    r = r % 10i32.pow(digit + 1);
-    r = r / 10i32.pow(digit);
+    r %= 10i64.pow(digit + 1);
+    r /= 10i64.pow(digit);
 
     r
 }
 
-#[derive(Debug, PartialEq)]
-enum Param {
-    Pos(i32),
-    Inter(i32),
+#[inline]
+fn addr<W: Word>(word: &W) -> Result<usize, Fault<W>> {
+    match word.to_addr() {
+        Some(addr) => Ok(addr),
+        None => Err(Fault::InvalidAddress(word.clone())),
+    }
+}
+
+#[inline]
+fn checked<W>(result: Option<W>) -> Result<W, Fault<W>> {
+    result.ok_or(Fault::Overflow)
 }
 
-impl Display for Param {
+#[derive(Debug, PartialEq, Clone)]
+pub enum Param<W> {
+    Pos(W),
+    Inter(W),
+    Rel(W),
+}
+
+impl<W: Word> Display for Param<W> {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
         match self {
-            Param::Pos(addr) => write!(fmt, "%0x{:04x}", addr),
-            Param::Inter(val) => write!(fmt, "0x{:04x}", val),
+            Param::Pos(addr) => write!(fmt, "%{}", Hex(addr)),
+            Param::Inter(val) => write!(fmt, "{}", Hex(val)),
+            Param::Rel(off) if *off < W::from(0) => write!(fmt, "%rb{}", Hex(off)),
+            Param::Rel(off) => write!(fmt, "%rb+{}", Hex(off)),
         }?;
 
         Ok(())
     }
 }
 
-impl Param {
-    fn digit(code: i32, encode: i32) -> Param {
+impl<W: Word> Param<W> {
+    /// The mode digit this parameter is encoded with.
+    pub fn mode(&self) -> i64 {
+        match self {
+            Param::Pos(_) => 0,
+            Param::Inter(_) => 1,
+            Param::Rel(_) => 2,
+        }
+    }
+
+    fn digit(code: W, encode: i64) -> Result<Param<W>, Fault<W>> {
         match encode {
-            0 => Param::Pos(code),
-            1 => Param::Inter(code),
-            _ => panic!("Invalid Argument mode: {}", encode),
+            0 => Ok(Param::Pos(code)),
+            1 => Ok(Param::Inter(code)),
+            2 => Ok(Param::Rel(code)),
+            _ => Err(Fault::InvalidMode(encode)),
         }
     }
 
-    fn get(&self, vm: &IntCode) -> i32 {
+    fn addr(&self, vm: &IntCode<W>) -> Result<usize, Fault<W>> {
         match self {
-            Param::Pos(addr) => vm[*addr],
-            Param::Inter(val) => *val,
+            Param::Pos(pos) => addr(pos),
+            Param::Inter(_) => Err(Fault::ImmediateWrite),
+            Param::Rel(off) => addr(&checked(vm.rb.checked_add(off))?),
         }
     }
 
-    fn set(&self, vm: &mut IntCode, value: i32) {
+    fn get(&self, vm: &IntCode<W>) -> Result<W, Fault<W>> {
         match self {
-            Param::Pos(addr) => vm[*addr] = value,
-            Param::Inter(_) => panic!("can not set in intermediate mode"),
+            Param::Inter(val) => Ok(val.clone()),
+            _ => Ok(vm[self.addr(vm)?].clone()),
+        }
+    }
+
+    fn set(&self, vm: &mut IntCode<W>, value: W) -> Result<(), Fault<W>> {
+        let addr = self.addr(vm)?;
+        vm.store(addr, value);
+
+        Ok(())
+    }
+
+    /// Operand value for traces, `?` if it can not be resolved.
+    fn show(&self, vm: &IntCode<W>) -> String {
+        match self.get(vm) {
+            Ok(val) => val.to_string(),
+            Err(_) => "?".to_string(),
+        }
+    }
+
+    /// Resolved address of a memory operand, formatted as positional.
+    fn real(&self, vm: &IntCode<W>) -> String {
+        match (self, self.addr(vm)) {
+            (Param::Inter(val), _) => format!("{}", Hex(val)),
+            (_, Ok(addr)) => format!("%0x{:04x}", addr),
+            (_, Err(_)) => "%?".to_string(),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
-enum OpCode {
-    Add(Param, Param, Param),
-    Mult(Param, Param, Param),
-    Input(Param),
-    Output(Param),
-    JumpTrue(Param, Param),
-    JumpFalse(Param, Param),
-    LessThan(Param, Param, Param),
-    Equals(Param, Param, Param),
+#[derive(Debug, PartialEq, Clone)]
+pub enum OpCode<W> {
+    Add(Param<W>, Param<W>, Param<W>),
+    Mult(Param<W>, Param<W>, Param<W>),
+    Input(Param<W>),
+    Output(Param<W>),
+    JumpTrue(Param<W>, Param<W>),
+    JumpFalse(Param<W>, Param<W>),
+    LessThan(Param<W>, Param<W>, Param<W>),
+    Equals(Param<W>, Param<W>, Param<W>),
+    AdjustBase(Param<W>),
 
     Quit,
 }
 
-impl Display for OpCode {
+impl<W: Word> Display for OpCode<W> {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        let params: Vec<String> = self.params().iter().map(|p| p.to_string()).collect();
+
+        if params.is_empty() {
+            write!(fmt, "{}", self.mnemonic())
+        } else {
+            write!(fmt, "{:<4} {}", self.mnemonic(), params.join(", "))
+        }
+    }
+}
+
+impl<W: Word> OpCode<W> {
+    /// Decodes the instruction at `ip`.
+    pub(crate) fn decode(mem: &Memory<W>, ip: usize) -> Result<OpCode<W>, Fault<W>> {
+        let code = match mem.get(ip).to_code() {
+            Some(code) => code,
+            None => return Err(Fault::UnknownOpcode),
+        };
+        let op = code % 100;
+
+        let opcode = match op {
+            1 => OpCode::Add(
+                param_arg!(mem, ip, code, 0),
+                param_arg!(mem, ip, code, 1),
+                param_arg!(mem, ip, code, 2),
+            ),
+            2 => OpCode::Mult(
+                param_arg!(mem, ip, code, 0),
+                param_arg!(mem, ip, code, 1),
+                param_arg!(mem, ip, code, 2),
+            ),
+            3 => OpCode::Input(param_arg!(mem, ip, code, 0)),
+            4 => OpCode::Output(param_arg!(mem, ip, code, 0)),
+            5 => OpCode::JumpTrue(param_arg!(mem, ip, code, 0), param_arg!(mem, ip, code, 1)),
+            6 => OpCode::JumpFalse(param_arg!(mem, ip, code, 0), param_arg!(mem, ip, code, 1)),
+            7 => OpCode::LessThan(
+                param_arg!(mem, ip, code, 0),
+                param_arg!(mem, ip, code, 1),
+                param_arg!(mem, ip, code, 2),
+            ),
+            8 => OpCode::Equals(
+                param_arg!(mem, ip, code, 0),
+                param_arg!(mem, ip, code, 1),
+                param_arg!(mem, ip, code, 2),
+            ),
+            9 => OpCode::AdjustBase(param_arg!(mem, ip, code, 0)),
+            99 => OpCode::Quit,
+            _ => return Err(Fault::UnknownOpcode),
+        };
+
+        Ok(opcode)
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
         use OpCode::*;
+
         match self {
-            Add(a, b, o) => write!(fmt, "ADD  {}, {}, {}", a, b, o)?,
-            Mult(a, b, o) => write!(fmt, "MULT {}, {}, {}", a, b, o)?,
-            Input(a) => write!(fmt, "IN   {}", a)?,
-            Output(a) => write!(fmt, "OUT  {}", a)?,
-            JumpTrue(a, b) => write!(fmt, "JT   {}, {}", a, b)?,
-            JumpFalse(a, b) => write!(fmt, "JF   {}, {}", a, b)?,
-            LessThan(a, b, o) => write!(fmt, "LT   {}, {}, {}", a, b, o)?,
-            Equals(a, b, o) => write!(fmt, "EQ   {}, {}, {}", a, b, o)?,
-            Quit => write!(fmt, "QT")?,
+            Add(_, _, _) => "ADD",
+            Mult(_, _, _) => "MULT",
+            Input(_) => "IN",
+            Output(_) => "OUT",
+            JumpTrue(_, _) => "JT",
+            JumpFalse(_, _) => "JF",
+            LessThan(_, _, _) => "LT",
+            Equals(_, _, _) => "EQ",
+            AdjustBase(_) => "ARB",
+            Quit => "QT",
+        }
+    }
+
+    pub fn params(&self) -> Vec<&Param<W>> {
+        use OpCode::*;
+
+        match self {
+            Add(a, b, c) | Mult(a, b, c) | LessThan(a, b, c) | Equals(a, b, c) => vec![a, b, c],
+            JumpTrue(a, b) | JumpFalse(a, b) => vec![a, b],
+            Input(a) | Output(a) | AdjustBase(a) => vec![a],
+            Quit => vec![],
+        }
+    }
+
+    /// Number of words the instruction occupies.
+    pub fn size(&self) -> usize {
+        use OpCode::*;
+
+        match self {
+            Add(_, _, _) | Mult(_, _, _) | LessThan(_, _, _) | Equals(_, _, _) => 4,
+            JumpTrue(_, _) | JumpFalse(_, _) => 3,
+            Input(_) | Output(_) | AdjustBase(_) => 2,
+            Quit => 1,
+        }
+    }
+
+    /// The canonical instruction word encoding this opcode and its modes.
+    pub fn code(&self) -> i64 {
+        use OpCode::*;
+
+        let op = match self {
+            Add(_, _, _) => 1,
+            Mult(_, _, _) => 2,
+            Input(_) => 3,
+            Output(_) => 4,
+            JumpTrue(_, _) => 5,
+            JumpFalse(_, _) => 6,
+            LessThan(_, _, _) => 7,
+            Equals(_, _, _) => 8,
+            AdjustBase(_) => 9,
+            Quit => 99,
         };
 
-        Ok(())
+        self.params()
+            .iter()
+            .enumerate()
+            .fold(op, |code, (i, param)| {
+                code + param.mode() * 10i64.pow(i as u32 + 2)
+            })
     }
-}
 
-impl OpCode {
-    fn effect(&self, vm: &mut IntCode) -> Option<i32> {
+    fn effect(&self, vm: &mut IntCode<W>) -> Result<Flow<W>, Fault<W>> {
         use OpCode::*;
         match self {
             Add(a, b, o) => {
-                o.set(vm, a.get(vm) + b.get(vm));
-                None
+                o.set(vm, checked(a.get(vm)?.checked_add(&b.get(vm)?))?)?;
+                Ok(Flow::Next)
             }
             Mult(a, b, o) => {
-                o.set(vm, a.get(vm) * b.get(vm));
-                None
-            }
-            Input(o) => {
-                let v = vm.input.remove(0);
-                o.set(vm, v);
-                None
+                o.set(vm, checked(a.get(vm)?.checked_mul(&b.get(vm)?))?)?;
+                Ok(Flow::Next)
             }
+            Input(o) => match vm.next_input() {
+                Some(v) => {
+                    o.set(vm, v)?;
+                    Ok(Flow::Next)
+                }
+                None => Ok(Flow::Halt(Halt::Input)),
+            },
             Output(i) => {
-                let v = i.get(vm);
-                Some(v)
+                let v = i.get(vm)?;
+
+                match &mut vm.sink {
+                    Some(sink) => {
+                        sink.send(v);
+                        Ok(Flow::Next)
+                    }
+                    None => Ok(Flow::Halt(Halt::Output(v))),
+                }
             }
-            JumpTrue(val, addr) => {
-                if val.get(vm) != 0 {
-                    vm.ip = addr.get(vm)
+            JumpTrue(val, dest) => {
+                if val.get(vm)? != W::from(0) {
+                    Ok(Flow::Jump(addr(&dest.get(vm)?)?))
+                } else {
+                    Ok(Flow::Next)
                 }
-
-                None
             }
 
-            JumpFalse(val, addr) => {
-                if val.get(vm) == 0 {
-                    vm.ip = addr.get(vm)
+            JumpFalse(val, dest) => {
+                if val.get(vm)? == W::from(0) {
+                    Ok(Flow::Jump(addr(&dest.get(vm)?)?))
+                } else {
+                    Ok(Flow::Next)
                 }
-
-                None
             }
 
             LessThan(a, b, o) => {
-                if a.get(vm) < b.get(vm) {
-                    o.set(vm, 1)
+                if a.get(vm)? < b.get(vm)? {
+                    o.set(vm, W::from(1))?
                 } else {
-                    o.set(vm, 0)
+                    o.set(vm, W::from(0))?
                 }
 
-                None
+                Ok(Flow::Next)
             }
 
             Equals(a, b, o) => {
-                if a.get(vm) == b.get(vm) {
-                    o.set(vm, 1)
+                if a.get(vm)? == b.get(vm)? {
+                    o.set(vm, W::from(1))?
                 } else {
-                    o.set(vm, 0)
+                    o.set(vm, W::from(0))?
                 }
 
-                None
+                Ok(Flow::Next)
+            }
+
+            AdjustBase(a) => {
+                vm.rb = checked(vm.rb.checked_add(&a.get(vm)?))?;
+                Ok(Flow::Next)
             }
 
             Quit => {
                 vm.on = false;
-                None
+                Ok(Flow::Halt(Halt::Quit))
             }
         }
     }
 
-    fn real(&self, vm: &IntCode) -> String {
+    /// The operand values an instruction would use on the given machine.
+    pub fn real(&self, vm: &IntCode<W>) -> String {
         use OpCode::*;
         match self {
-            Add(a, b, _) => format!("{} + {}", a.get(vm), b.get(vm)),
-            Mult(a, b, _) => format!("{} * {}", a.get(vm), b.get(vm)),
-            Input(_) => format!(""),
-            Output(a) => format!("{}", a.get(vm)),
-            JumpTrue(a, b) => format!("{}, {}", a.get(vm), b.get(vm)),
-            JumpFalse(a, b) => format!("{}, {}", a.get(vm), b.get(vm)),
-            LessThan(a, b, _) => format!("{} < {}", a.get(vm), b.get(vm)),
-            Equals(a, b, _) => format!("{} = {}", a.get(vm), b.get(vm)),
-            Quit => format!(""),
+            Add(a, b, o) => format!("{} + {} -> {}", a.show(vm), b.show(vm), o.real(vm)),
+            Mult(a, b, o) => format!("{} * {} -> {}", a.show(vm), b.show(vm), o.real(vm)),
+            Input(o) => format!("-> {}", o.real(vm)),
+            Output(a) => a.show(vm),
+            JumpTrue(a, b) => format!("{}, {}", a.show(vm), b.show(vm)),
+            JumpFalse(a, b) => format!("{}, {}", a.show(vm), b.show(vm)),
+            LessThan(a, b, o) => format!("{} < {} -> {}", a.show(vm), b.show(vm), o.real(vm)),
+            Equals(a, b, o) => format!("{} = {} -> {}", a.show(vm), b.show(vm), o.real(vm)),
+            AdjustBase(a) => format!("{} + {}", vm.rb, a.show(vm)),
+            Quit => String::new(),
         }
     }
 
-    fn stride(&self, vm: &IntCode) -> Option<usize> {
+    /// The parameters an instruction reads from, in order.
+    fn reads(&self) -> Vec<&Param<W>> {
         use OpCode::*;
 
         match self {
-            Add(_, _, _) | Mult(_, _, _) => Some(4),
-            LessThan(_, _, _) | Equals(_, _, _) => Some(4),
-            Input(_) | Output(_) => Some(2),
-            JumpTrue(val, _) if val.get(vm) == 0 => Some(3),
-            JumpFalse(val, _) if val.get(vm) != 0 => Some(3),
-            Quit => Some(1),
-
-            // Auto Jumps
-            JumpTrue(_, _) => None,
-            JumpFalse(_, _) => None,
+            Add(a, b, _) | Mult(a, b, _) => vec![a, b],
+            LessThan(a, b, _) | Equals(a, b, _) => vec![a, b],
+            JumpTrue(a, b) | JumpFalse(a, b) => vec![a, b],
+            Output(a) | AdjustBase(a) => vec![a],
+            Input(_) | Quit => vec![],
         }
     }
 }
 
-impl<'a> IntCode {
-    pub fn new(space: Vec<i32>, input: Vec<i32>) -> IntCode {
+impl<W: Word> IntCode<W> {
+    /// Creates a machine with dense memory loaded from a
+    /// `Program` or plain `Vec` image, or with memory shared
+    /// copy on write with other machines from an `&Image`.
+    pub fn new<P: Into<Memory<W>>>(program: P, input: Vec<W>) -> IntCode<W> {
+        IntCode::with_memory(program.into(), input)
+    }
+
+    /// Creates a machine over a specific memory backend,
+    /// e.g. `Memory::sparse` for programs touching huge addresses.
+    pub fn with_memory(mem: Memory<W>, input: Vec<W>) -> IntCode<W> {
         let on = true;
+        let faulted = false;
         let ip = 0;
+        let rb = W::from(0);
+        let input = input.into();
+        let source = None;
+        let sink = None;
+        let budget = None;
+        let loops = None;
+        let breakpoints = BTreeSet::new();
+        let on_break = false;
+        let watch = Watcher::new();
+        let hit = None;
+        let tracer = None;
+        let writes = Vec::new();
+        let history = None;
+        let cache = Cache::new(&mem);
+        let profile = None;
+        let coverage = None;
 
         IntCode {
-            space,
+            mem,
+            cache,
             on,
+            faulted,
             ip,
+            rb,
             input,
+            source,
+            sink,
+            budget,
+            loops,
+            breakpoints,
+            on_break,
+            watch,
+            hit,
+            tracer,
+            writes,
+            history,
+            profile,
+            coverage,
         }
     }
 
-    pub fn run(&mut self) -> Vec<i32> {
-        let mut output = Vec::new();
-        while self.on {
-            let opcode = self.decode_op();
+    /// Creates a machine that calls `input` each time it needs an input value.
+    /// Returning `None` halts the machine with `Halt::Input`.
+    pub fn with_input_fn<P, F>(program: P, input: F) -> IntCode<W>
+    where
+        P: Into<Memory<W>>,
+        F: FnMut() -> Option<W> + Send + 'static,
+    {
+        let mut machine = IntCode::new(program, vec![]);
+        machine.input_from(device::source_fn(input));
+
+        machine
+    }
+
+    /// Limits execution to `steps` more instructions, halting with
+    /// `Halt::Budget` once they are spent. `None` removes the limit.
+    pub fn set_budget(&mut self, steps: Option<u64>) {
+        self.budget = steps;
+    }
+
+    /// Instructions left before the budget runs out, if one is set.
+    pub fn budget(&self) -> Option<u64> {
+        self.budget
+    }
+
+    /// Halts with `Halt::Loop` when the machine returns to an identical
+    /// state (ip, relative base and memory) without reading input in between.
+    ///
+    /// This costs a memory comparison whenever ip and relative base repeat.
+    pub fn detect_loops(&mut self, enabled: bool) {
+        self.loops = if enabled {
+            Some(LoopDetector::new())
+        } else {
+            None
+        };
+    }
+
+    /// Makes `exec` halt with `Halt::Breakpoint` before executing
+    /// the instruction at `addr`.
+    pub fn set_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.insert(addr);
+    }
+
+    /// Removes the breakpoint at `addr`, returning whether there was one.
+    pub fn clear_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    /// Addresses with a breakpoint, in ascending order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Makes the machine halt with `Halt::Watch` after an instruction
+    /// accesses `addr` as given, replacing any watchpoint already there.
+    pub fn watch(&mut self, addr: usize, watch: Watch) {
+        self.watch.set(addr, watch);
+    }
+
+    /// Removes the watchpoint at `addr`, returning whether there was one.
+    pub fn unwatch(&mut self, addr: usize) -> bool {
+        self.watch.clear(addr)
+    }
+
+    /// Watched addresses, in ascending order.
+    pub fn watchpoints(&self) -> impl Iterator<Item = (usize, Watch)> + '_ {
+        self.watch.points()
+    }
+
+    /// Halts with `Halt::Watch` when an instruction writes into
+    /// an instruction executed since this was enabled.
+    pub fn watch_code(&mut self, enabled: bool) {
+        self.watch.watch_code(enabled);
+    }
+
+    /// Logs every executed instruction so it can be undone with
+    /// `step_back`, `exec_back` or `rewind_to_output`.
+    ///
+    /// The log grows with every instruction until disabled,
+    /// see `record_recent_history` to bound it.
+    pub fn record_history(&mut self, enabled: bool) {
+        self.history = if enabled {
+            Some(History::new(None))
+        } else {
+            None
+        };
+    }
+
+    /// Logs executed instructions like `record_history`, but keeps only the
+    /// last `limit` of them, dropping the oldest.
+    pub fn record_recent_history(&mut self, limit: usize) {
+        self.history = Some(History::new(Some(limit)));
+    }
+
+    /// Number of instructions that can be undone.
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, History::len)
+    }
+
+    /// Undoes the last executed instruction, returning false if there is
+    /// none logged. Consumed input is put back in front of the input queue,
+    /// outputs already given to a sink can not be taken back.
+    pub fn step_back(&mut self) -> bool {
+        self.undo().is_some()
+    }
+
+    /// Undoes instructions until ip reaches a breakpoint, returning false
+    /// if the start of the history was reached instead.
+    pub fn exec_back(&mut self) -> bool {
+        while self.undo().is_some() {
+            if self.breakpoints.contains(&self.ip) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Undoes instructions up to and including the output instruction
+    /// that produced output `index`, counting from when history was enabled.
+    /// Nothing is undone and false returned if that output is not logged,
+    /// including when it was dropped from a limited history.
+    pub fn rewind_to_output(&mut self, index: usize) -> bool {
+        match &self.history {
+            Some(history) if history.first_output() <= index && index < history.outputs() => {}
+            _ => return false,
+        }
+
+        while let Some(output) = self.undo() {
+            if output && self.history.as_ref().map_or(0, History::outputs) == index {
+                break;
+            }
+        }
+
+        true
+    }
+
+    /// Reverts the last logged instruction, returning whether it produced output.
+    fn undo(&mut self) -> Option<bool> {
+        let entry = self.history.as_mut()?.pop()?;
+
+        for (addr, old) in entry.writes.into_iter().rev() {
+            self.cache.invalidate(addr);
+            *self.mem.get_mut(addr) = old;
+        }
+
+        if let Some(input) = entry.input {
+            self.input.push_front(input);
+        }
+
+        self.ip = entry.ip;
+        self.rb = entry.rb;
+        self.on = true;
+        self.faulted = false;
+        self.on_break = true;
+        self.hit = None;
+
+        if let Some(loops) = &mut self.loops {
+            loops.reset();
+        }
+
+        Some(entry.output)
+    }
+
+    /// Counts executed instructions, memory reads and writes from now on,
+    /// see `profile`. Disabling discards the counts.
+    pub fn record_profile(&mut self, enabled: bool) {
+        self.profile = if enabled {
+            Some(Profile::default())
+        } else {
+            None
+        };
+    }
+
+    /// Execution counts since profiling was enabled.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Decoded instructions are cached and reused until their code is
+    /// written, which is on by default. Disabling decodes every step.
+    pub fn cache_instructions(&mut self, enabled: bool) {
+        self.cache.enable(enabled);
+    }
+
+    /// Records executed instructions and branch directions from now on,
+    /// see `coverage`. Disabling discards the coverage.
+    pub fn record_coverage(&mut self, enabled: bool) {
+        self.coverage = if enabled {
+            Some(Coverage::default())
+        } else {
+            None
+        };
+    }
+
+    /// Coverage since recording was enabled, to `merge` across runs.
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Reads input from `source` once the pushed input queue is exhausted,
+    /// replacing any source already attached.
+    pub fn input_from<S: Source<W> + 'static>(&mut self, source: S) {
+        self.source = Some(Box::new(source));
+    }
+
+    /// Sends outputs to `sink` as they are produced,
+    /// replacing any sink already attached.
+    pub fn output_to<S: Sink<W> + 'static>(&mut self, sink: S) {
+        self.sink = Some(Box::new(sink));
+    }
+
+    /// Detaches the input source and output sink, if any.
+    pub fn disconnect(&mut self) {
+        self.source = None;
+        self.sink = None;
+    }
+
+    /// Sends a record of every executed instruction to `tracer`,
+    /// replacing any tracer already attached.
+    pub fn trace<T: Tracer<W> + 'static>(&mut self, tracer: T) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    /// Detaches the current tracer, if any.
+    pub fn untrace(&mut self) {
+        self.tracer = None;
+    }
+
+    /// Queues a value to be consumed by the next input instruction.
+    pub fn push_input(&mut self, value: W) {
+        self.input.push_back(value);
+    }
+
+    /// Whether the machine has executed a quit instruction.
+    pub fn halted(&self) -> bool {
+        !self.on
+    }
+
+    /// Address of the next instruction to execute.
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    /// Base address of relative mode parameters.
+    pub fn relative_base(&self) -> &W {
+        &self.rb
+    }
+
+    pub fn memory(&self) -> &Memory<W> {
+        &self.mem
+    }
+
+    /// Decodes the instruction at `addr` without executing it.
+    pub fn decode(&self, addr: usize) -> Result<OpCode<W>, MachineError<W>> {
+        OpCode::decode(&self.mem, addr).map_err(|fault| fault.at(addr, self[addr].clone()))
+    }
+
+    /// Pushed input that has not been consumed yet.
+    pub fn pending_input(&self) -> impl Iterator<Item = &W> {
+        self.input.iter()
+    }
+
+    /// Captures the machine's execution state.
+    pub fn snapshot(&self) -> Snapshot<W> {
+        Snapshot {
+            memory: self.mem.clone(),
+            ip: self.ip,
+            rb: self.rb.clone(),
+            input: self.input.iter().cloned().collect(),
+            halted: !self.on,
+        }
+    }
+
+    /// Returns the machine to a captured execution state.
+    ///
+    /// Attached devices, tracers and the budget are kept as they are.
+    pub fn restore(&mut self, snapshot: &Snapshot<W>) {
+        self.mem = snapshot.memory.clone();
+        self.cache.reset(&self.mem);
+        self.ip = snapshot.ip;
+        self.rb = snapshot.rb.clone();
+        self.input = snapshot.input.iter().cloned().collect();
+        self.on = !snapshot.halted;
+        self.faulted = false;
+        self.on_break = false;
+        self.hit = None;
+        self.watch.reset();
+
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+
+        if let Some(loops) = &mut self.loops {
+            loops.reset();
+        }
+    }
+
+    /// Creates a machine resuming from a captured execution state.
+    pub fn from_snapshot(snapshot: Snapshot<W>) -> IntCode<W> {
+        let mut machine = IntCode::with_memory(snapshot.memory, snapshot.input);
+        machine.ip = snapshot.ip;
+        machine.rb = snapshot.rb;
+        machine.on = !snapshot.halted;
+
+        machine
+    }
+
+    /// Executes a single instruction.
+    ///
+    /// Returns the halt reason if the instruction produced output, quit,
+    /// or could not run because input is required.
+    pub fn step(&mut self) -> Result<Option<Halt<W>>, MachineError<W>> {
+        if let Some(hit) = self.hit.take() {
+            return Ok(Some(Halt::Watch(hit)));
+        }
+
+        if !self.on {
+            return Ok(Some(Halt::Quit));
+        }
+
+        if self.budget == Some(0) {
+            return Ok(Some(Halt::Budget));
+        }
 
-            if cfg!(debug_assertions) {
-                eprintln!("0x{:04x}: {} ({})", self.ip, opcode, opcode.real(self));
+        if let Some(loops) = &mut self.loops {
+            if loops.check(self.ip, &self.rb, &self.mem) {
+                return Ok(Some(Halt::Loop));
             }
+        }
 
-            if let Some(out) = opcode.effect(self) {
-                output.push(out);
+        let ip = self.ip;
+        let halt = self
+            .try_step()
+            .map_err(|fault| fault.at(ip, self[ip].clone()))?;
+
+        match halt {
+            Some(Halt::Input) => {
+                if let Some(loops) = &mut self.loops {
+                    loops.reset();
+                }
             }
 
-            if let Some(stride) = opcode.stride(self) {
-                self.ip += stride as i32;
+            _ => {
+                self.on_break = false;
+
+                if let Some(budget) = &mut self.budget {
+                    *budget -= 1;
+                }
             }
         }
 
-        output
+        match (halt, self.hit.take()) {
+            (None, Some(hit)) => Ok(Some(Halt::Watch(hit))),
+            (halt, hit) => {
+                self.hit = hit;
+                Ok(halt)
+            }
+        }
     }
 
-    fn decode_op(&self) -> OpCode {
-        let code = self[self.ip];
-        let op = code % 100;
+    fn try_step(&mut self) -> Result<Option<Halt<W>>, Fault<W>> {
+        let ip = self.ip;
+        let opcode = self.cache.decode(&self.mem, ip)?;
+        let after = ip.checked_add(opcode.size()).ok_or(Fault::EndOfMemory)?;
+        self.writes.clear();
 
-        match op {
-            1 => OpCode::Add(
-                param_arg!(self, code, 0),
-                param_arg!(self, code, 1),
-                param_arg!(self, code, 2),
-            ),
-            2 => OpCode::Mult(
-                param_arg!(self, code, 0),
-                param_arg!(self, code, 1),
-                param_arg!(self, code, 2),
-            ),
-            3 => OpCode::Input(param_arg!(self, code, 0)),
-            4 => OpCode::Output(param_arg!(self, code, 0)),
-            5 => OpCode::JumpTrue(param_arg!(self, code, 0), param_arg!(self, code, 1)),
-            6 => OpCode::JumpFalse(param_arg!(self, code, 0), param_arg!(self, code, 1)),
-            7 => OpCode::LessThan(
-                param_arg!(self, code, 0),
-                param_arg!(self, code, 1),
-                param_arg!(self, code, 2),
-            ),
-            8 => OpCode::Equals(
-                param_arg!(self, code, 0),
-                param_arg!(self, code, 1),
-                param_arg!(self, code, 2),
-            ),
-            99 => OpCode::Quit,
-            unrecognized => panic!("unrecognized opcode: {}", unrecognized),
+        let operands = match self.tracer {
+            Some(_) => opcode
+                .reads()
+                .into_iter()
+                .map(|param| param.get(self))
+                .collect::<Result<Vec<W>, Fault<W>>>()?,
+            None => Vec::new(),
+        };
+
+        if self.watch.active() {
+            self.watch.take();
+            self.watch.execute(ip, opcode.size());
+        }
+
+        // memory cells read, resolved before the instruction can move rb
+        let reads = if self.watch.active() || self.profile.is_some() {
+            opcode
+                .reads()
+                .into_iter()
+                .filter(|param| !matches!(param, Param::Inter(_)))
+                .map(|param| Ok((param.addr(self)?, param.get(self)?)))
+                .collect::<Result<Vec<(usize, W)>, Fault<W>>>()?
+        } else {
+            Vec::new()
+        };
+
+        if let Some(history) = &mut self.history {
+            history.begin(ip, &self.rb);
         }
+
+        let flow = opcode.effect(self);
+
+        if let (Ok(Flow::Halt(Halt::Input)) | Err(_), Some(history)) = (&flow, &mut self.history) {
+            history.abort();
+        }
+
+        let (next, halt) = match flow? {
+            Flow::Next => (None, None),
+            Flow::Jump(target) => (Some(target), None),
+            Flow::Halt(Halt::Input) => return Ok(Some(Halt::Input)),
+            Flow::Halt(halt) => (None, Some(halt)),
+        };
+
+        if let Some(history) = &mut self.history {
+            history.commit(matches!(opcode, OpCode::Output(_)));
+        }
+
+        if let Some(profile) = &mut self.profile {
+            profile.execute(ip, &opcode);
+
+            for (addr, _) in &reads {
+                profile.read(*addr);
+            }
+        }
+
+        for (addr, value) in &reads {
+            self.watch.read(*addr, value);
+        }
+
+        if let Some((addr, access, old, new)) = self.watch.take() {
+            self.hit = Some(Watchpoint {
+                ip,
+                op: opcode.clone(),
+                addr,
+                access,
+                old,
+                new,
+            });
+        }
+
+        if let Some(tracer) = &mut self.tracer {
+            let writes = std::mem::take(&mut self.writes);
+            let record = Record {
+                ip,
+                op: opcode.clone(),
+                operands,
+                writes,
+            };
+
+            tracer.record(&record);
+        }
+
+        if let Some(coverage) = &mut self.coverage {
+            coverage.execute(ip);
+            coverage.branch(ip, &opcode, next.is_some());
+        }
+
+        self.ip = next.unwrap_or(after);
+
+        Ok(halt)
     }
-}
 
-impl std::ops::Index<i32> for IntCode {
-    type Output = i32;
+    /// Executes instructions until the machine halts for any reason.
+    ///
+    /// Execution may be resumed from the same point by calling `exec`
+    /// again, e.g. after pushing more input.
+    pub fn exec(&mut self) -> Result<Halt<W>, MachineError<W>> {
+        loop {
+            if self.break_here() {
+                return Ok(Halt::Breakpoint);
+            }
 
-    fn index(&self, pos: i32) -> &i32 {
-        if pos < 0 {
-            panic!("addresses may not be negative")
+            if let Some(halt) = self.step()? {
+                return Ok(halt);
+            }
         }
+    }
 
-        &self.space[pos as usize]
+    /// Whether the next instruction is at a breakpoint that has not been
+    /// stopped on yet. Stops on it, so resuming runs past it.
+    pub(crate) fn break_here(&mut self) -> bool {
+        if self.on && !self.on_break && self.breakpoints.contains(&self.ip) {
+            self.on_break = true;
+            return true;
+        }
+
+        false
+    }
+
+    /// Runs the machine until it halts for any reason other than output,
+    /// such as quitting or blocking on input, returning the outputs produced along the way.
+    pub fn run(&mut self) -> Result<Vec<W>, MachineError<W>> {
+        let mut output = Vec::new();
+
+        while let Halt::Output(out) = self.exec()? {
+            output.push(out);
+        }
+
+        Ok(output)
+    }
+
+    fn next_input(&mut self) -> Option<W> {
+        let value = match self.input.pop_front() {
+            Some(value) => Some(value),
+            None => self.source.as_mut().and_then(|source| source.next()),
+        };
+
+        if let (Some(_), Some(loops)) = (&value, &mut self.loops) {
+            loops.reset();
+        }
+
+        if let (Some(value), Some(history)) = (&value, &mut self.history) {
+            history.input(value);
+        }
+
+        value
+    }
+
+    /// Writes a cell on behalf of an instruction.
+    fn store(&mut self, addr: usize, value: W) {
+        if self.tracer.is_some() {
+            self.writes.push((addr, value.clone()));
+        }
+
+        if self.watch.active() {
+            self.watch.write(addr, self.mem.get(addr), &value);
+        }
+
+        if let Some(history) = &mut self.history {
+            history.write(addr, self.mem.get(addr));
+        }
+
+        if let Some(profile) = &mut self.profile {
+            profile.write(addr);
+        }
+
+        self.cache.invalidate(addr);
+        *self.mem.get_mut(addr) = value;
     }
 }
 
-impl std::ops::IndexMut<i32> for IntCode {
-    fn index_mut(&mut self, pos: i32) -> &mut i32 {
-        if pos < 0 {
-            panic!("addresses may not be negative")
+impl<W: Word> std::fmt::Debug for IntCode<W> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        fmt.debug_struct("IntCode")
+            .field("mem", &self.mem)
+            .field("input", &self.input)
+            .field("on", &self.on)
+            .field("faulted", &self.faulted)
+            .field("ip", &self.ip)
+            .field("rb", &self.rb)
+            .field("source", &self.source.is_some())
+            .field("sink", &self.sink.is_some())
+            .field("breakpoints", &self.breakpoints)
+            .field("traced", &self.tracer.is_some())
+            .finish()
+    }
+}
+
+impl<W: Word> std::ops::Index<usize> for IntCode<W> {
+    type Output = W;
+
+    fn index(&self, pos: usize) -> &W {
+        self.mem.get(pos)
+    }
+}
+
+impl<W: Word> std::ops::IndexMut<usize> for IntCode<W> {
+    fn index_mut(&mut self, pos: usize) -> &mut W {
+        self.cache.invalidate(pos);
+        self.mem.get_mut(pos)
+    }
+}
+
+/// Yields each output as soon as it is produced.
+///
+/// Iteration ends when the machine halts for any other reason, e.g. quitting
+/// or needing input, and may be resumed after pushing more input. A fault is
+/// yielded once and ends iteration for good, though the machine itself is
+/// not halted: `exec` reports the fault again. Stepping back or restoring a
+/// snapshot allows iterating again. Outputs sent to an attached sink are not
+/// yielded.
+impl<W: Word> Iterator for IntCode<W> {
+    type Item = Result<W, MachineError<W>>;
+
+    fn next(&mut self) -> Option<Result<W, MachineError<W>>> {
+        if self.faulted {
+            return None;
         }
 
-        &mut self.space[pos as usize]
+        match self.exec() {
+            Ok(Halt::Output(value)) => Some(Ok(value)),
+            Ok(_) => None,
+            Err(err) => {
+                self.faulted = true;
+                Some(Err(err))
+            }
+        }
     }
 }
 
@@ -294,12 +1133,12 @@ mod test {
 
     #[test]
     fn test_basic() {
-        let prog = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let prog: Vec<i32> = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
         let mut machine = IntCode::new(prog, vec![]);
 
-        machine.run();
+        machine.run().unwrap();
 
-        assert_eq!(machine.space[0], 3500);
+        assert_eq!(machine[0], 3500);
     }
 
     #[test]
@@ -307,6 +1146,337 @@ mod test {
         let prog = vec![3, 0, 4, 0, 99];
         let mut machine = IntCode::new(prog, vec![42]);
 
-        assert_eq!(machine.run(), vec![42]);
+        assert_eq!(machine.run().unwrap(), vec![42]);
+    }
+
+    #[test]
+    fn iterate_outputs() {
+        // echoes each input until it reads a zero
+        let prog = vec![3, 11, 1006, 11, 10, 4, 11, 1105, 1, 0, 99, 0];
+        let mut machine = IntCode::new(prog, vec![1, 2]);
+
+        let doubled: Vec<i64> = machine.by_ref().map(|out| out.unwrap() * 2).collect();
+        assert_eq!(doubled, vec![2, 4]);
+        assert!(!machine.halted());
+
+        machine.push_input(3);
+        machine.push_input(0);
+        assert_eq!(machine.collect::<Result<Vec<_>, _>>(), Ok(vec![3]));
+
+        let mut faulty = IntCode::new(vec![104, 7, 42], vec![]);
+        assert_eq!(faulty.next(), Some(Ok(7)));
+        assert!(matches!(
+            faulty.next(),
+            Some(Err(MachineError::UnknownOpcode { ip: 2, .. }))
+        ));
+        assert_eq!(faulty.next(), None);
+        assert!(!faulty.halted());
+        assert!(matches!(
+            faulty.exec(),
+            Err(MachineError::UnknownOpcode { ip: 2, .. })
+        ));
+
+        let faulty = IntCode::new(vec![104, 7, 42], vec![]);
+        assert_eq!(faulty.filter_map(Result::ok).collect::<Vec<i64>>(), vec![7]);
+    }
+
+    #[test]
+    fn input_fn() {
+        let mut count = 0;
+        let machine = IntCode::with_input_fn(
+            vec![3, 11, 1006, 11, 10, 4, 11, 1105, 1, 0, 99, 0],
+            move || {
+                count += 1;
+                Some(if count < 4 { count * 10 } else { 0 })
+            },
+        );
+
+        let outputs: Result<Vec<i64>, _> = machine.collect();
+        assert_eq!(outputs, Ok(vec![10, 20, 30]));
+    }
+
+    #[test]
+    fn resume_on_input() {
+        let prog = vec![3, 0, 4, 0, 3, 0, 4, 0, 99];
+        let mut machine = IntCode::new(prog, vec![1]);
+
+        assert_eq!(machine.exec().unwrap(), Halt::Output(1));
+        assert_eq!(machine.exec().unwrap(), Halt::Input);
+        assert_eq!(machine.exec().unwrap(), Halt::Input);
+
+        machine.push_input(2);
+
+        assert_eq!(machine.exec().unwrap(), Halt::Output(2));
+        assert_eq!(machine.exec().unwrap(), Halt::Quit);
+        assert!(machine.halted());
+        assert_eq!(machine.exec().unwrap(), Halt::Quit);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let prog = vec![3, 0, 4, 0, 3, 0, 4, 0, 99];
+        let mut machine = IntCode::new(prog, vec![1, 2]);
+        machine.set_breakpoint(2);
+        machine.set_breakpoint(4);
+
+        assert_eq!(machine.exec().unwrap(), Halt::Breakpoint);
+        assert_eq!(machine.ip(), 2);
+        assert_eq!(machine.exec().unwrap(), Halt::Output(1));
+        assert_eq!(machine.exec().unwrap(), Halt::Breakpoint);
+        assert_eq!(machine.ip(), 4);
+
+        assert!(machine.clear_breakpoint(2));
+        assert_eq!(machine.breakpoints().collect::<Vec<_>>(), vec![4]);
+        assert_eq!(machine.run().unwrap(), vec![2]);
+        assert!(machine.halted());
+    }
+
+    #[test]
+    fn run_stops_on_input() {
+        let prog = vec![4, 5, 3, 0, 99, 7];
+        let mut machine = IntCode::new(prog, vec![]);
+
+        assert_eq!(machine.run().unwrap(), vec![7]);
+        assert!(!machine.halted());
+
+        machine.push_input(1);
+
+        assert_eq!(machine.run().unwrap(), vec![]);
+        assert!(machine.halted());
+    }
+
+    #[test]
+    fn budget() {
+        let prog = vec![1001, 7, 1, 7, 1105, 1, 0, 0];
+        let mut machine = IntCode::new(prog, vec![]);
+
+        machine.set_budget(Some(5));
+
+        assert_eq!(machine.exec(), Ok(Halt::Budget));
+        assert_eq!(machine.exec(), Ok(Halt::Budget));
+        assert_eq!(machine.budget(), Some(0));
+        assert_eq!(machine[7], 3);
+        assert_eq!(machine.ip, 4);
+
+        machine.set_budget(Some(2));
+        assert_eq!(machine.exec(), Ok(Halt::Budget));
+        assert_eq!(machine[7], 4);
+    }
+
+    #[test]
+    fn infinite_loop() {
+        // toggles a cell between 0 and 1 forever
+        let prog = vec![1008, 7, 0, 7, 1105, 1, 0, 0];
+        let mut machine = IntCode::new(prog, vec![]);
+        machine.detect_loops(true);
+        machine.set_budget(Some(1000));
+
+        assert_eq!(machine.exec(), Ok(Halt::Loop));
+
+        // counts forever, never repeating a state
+        let prog = vec![1001, 7, 1, 7, 1105, 1, 0, 0];
+        let mut machine = IntCode::new(prog, vec![]);
+        machine.detect_loops(true);
+        machine.set_budget(Some(1000));
+
+        assert_eq!(machine.exec(), Ok(Halt::Budget));
+    }
+
+    #[test]
+    fn input_is_not_a_loop() {
+        let mut machine = IntCode::new(fixtures::ECHO.to_vec(), vec![1, 1, 1]);
+        machine.detect_loops(true);
+
+        assert_eq!(machine.run(), Ok(vec![1, 1, 1]));
+        assert_eq!(machine.exec(), Ok(Halt::Input));
+        assert_eq!(machine.exec(), Ok(Halt::Input));
+    }
+
+    #[test]
+    fn relative_base() {
+        let prog = vec![109, 9, 203, 2, 204, 2, 109, -3, 204, 5, 99, 0];
+        let mut machine = IntCode::new(prog, vec![17]);
+
+        assert_eq!(machine.run().unwrap(), vec![17, 17]);
+        assert_eq!(machine.rb, 6);
+    }
+
+    #[test]
+    fn relative_display() {
+        let prog = vec![109, 4, 22201, -2, 1, 1, 99];
+        let mut machine = IntCode::new(prog, vec![]);
+
+        machine.step().unwrap();
+        let op = machine.decode(machine.ip()).unwrap();
+
+        assert_eq!(
+            op,
+            OpCode::Add(Param::Rel(-2), Param::Rel(1), Param::Rel(1))
+        );
+        assert_eq!(op.to_string(), "ADD  %rb-0x0002, %rb+0x0001, %rb+0x0001");
+        assert_eq!(op.real(&machine), "22201 + 1 -> %0x0005");
+    }
+
+    #[test]
+    fn unknown_opcode() {
+        let prog = vec![1101, 1, 1, 5, 42, 0];
+        let mut machine = IntCode::new(prog, vec![]);
+
+        let err = machine.run().unwrap_err();
+
+        assert_eq!(err, MachineError::UnknownOpcode { ip: 4, instr: 42 });
+        assert_eq!(err.to_string(), "0x0004 (42): unrecognized opcode");
+    }
+
+    #[test]
+    fn faults() {
+        let mut machine = IntCode::new(vec![301, 0, 0, 0, 99], vec![]);
+        assert_eq!(
+            machine.run(),
+            Err(MachineError::InvalidMode {
+                ip: 0,
+                instr: 301,
+                mode: 3
+            })
+        );
+
+        let mut machine = IntCode::new(vec![11101, 0, 0, 0, 99], vec![]);
+        assert_eq!(
+            machine.run(),
+            Err(MachineError::ImmediateWrite {
+                ip: 0,
+                instr: 11101
+            })
+        );
+
+        let mut machine = IntCode::new(vec![4, -1, 99], vec![]);
+        assert_eq!(
+            machine.run(),
+            Err(MachineError::InvalidAddress {
+                ip: 0,
+                instr: 4,
+                addr: -1
+            })
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        // squares its input
+        let prog = vec![3, 9, 2, 9, 9, 9, 4, 9, 99, 0];
+
+        let mut machine: IntCode<i64> = IntCode::new(prog.clone(), vec![3037000499]);
+        assert_eq!(machine.run(), Ok(vec![9223372030926249001]));
+
+        let mut machine: IntCode<i64> = IntCode::new(prog, vec![3037000500]);
+        let err = machine.run().unwrap_err();
+        assert_eq!(err, MachineError::Overflow { ip: 2, instr: 2 });
+        assert_eq!(err.to_string(), "0x0002 (2): arithmetic overflow");
+
+        let mut machine: IntCode<i64> = IntCode::new(vec![1101, i64::MAX, 1, 0, 99], vec![]);
+        assert_eq!(
+            machine.run(),
+            Err(MachineError::Overflow { ip: 0, instr: 1101 })
+        );
+
+        let mut machine: IntCode<i64> = IntCode::new(vec![109, i64::MAX, 109, 1, 99], vec![]);
+        assert_eq!(
+            machine.run(),
+            Err(MachineError::Overflow { ip: 2, instr: 109 })
+        );
+    }
+}
+
+#[cfg(test)]
+mod wide_test {
+    use super::*;
+
+    #[test]
+    fn large_output() {
+        let prog = vec![104, 1125899906842624, 99];
+        let mut machine: IntCode<i64> = IntCode::new(prog, vec![]);
+
+        assert_eq!(machine.run().unwrap(), vec![1125899906842624]);
+    }
+
+    #[test]
+    fn sixteen_digits() {
+        let prog = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+        let mut machine: IntCode<i128> = IntCode::new(prog, vec![]);
+
+        assert_eq!(machine.run().unwrap(), vec![1219070632396864]);
+    }
+
+    #[test]
+    fn quine() {
+        let prog = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let mut machine: IntCode<i64> = IntCode::new(prog.clone(), vec![]);
+
+        assert_eq!(machine.run().unwrap(), prog);
+    }
+
+    #[test]
+    fn sparse_far_write() {
+        let prog = vec![1101, 20, 22, 1 << 45, 4, 1 << 45, 99];
+        let mut machine: IntCode<i64> = IntCode::with_memory(Memory::sparse(prog), vec![]);
+
+        assert_eq!(machine.run().unwrap(), vec![42]);
+    }
+
+    #[test]
+    fn last_address() {
+        let last = usize::MAX as i128;
+
+        let prog = vec![1101, 4, 0, last, 99];
+        let mut machine: IntCode<i128> = IntCode::new(prog.clone(), vec![]);
+        assert_eq!(machine.run(), Ok(vec![]));
+        let mut machine: IntCode<i128> = IntCode::with_memory(Memory::sparse(prog), vec![]);
+        assert_eq!(machine.run(), Ok(vec![]));
+
+        // an output whose operand would be past the last address
+        let mut machine: IntCode<i128> = IntCode::new(vec![1105, 1, last], vec![]);
+        machine[usize::MAX] = 4;
+        let err = machine.run().unwrap_err();
+        assert_eq!(
+            err,
+            MachineError::EndOfMemory {
+                ip: usize::MAX,
+                instr: 4
+            }
+        );
+
+        let mut machine: IntCode<i128> = IntCode::new(vec![1105, 1, last], vec![]);
+        machine[usize::MAX] = 99;
+        assert_eq!(machine.run().unwrap_err().ip(), usize::MAX);
+    }
+
+    #[test]
+    fn negative_hex() {
+        let op: OpCode<i64> = OpCode::Add(Param::Inter(-3), Param::Rel(-16), Param::Pos(2));
+
+        assert_eq!(op.to_string(), "ADD  -0x0003, %rb-0x0010, %0x0002");
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint() {
+        use num_bigint::BigInt;
+
+        let big: BigInt = "1000000000000000000000000".parse().unwrap();
+        let prog = vec![
+            BigInt::from(2),
+            BigInt::from(5),
+            BigInt::from(5),
+            BigInt::from(6),
+            BigInt::from(99),
+            big.clone(),
+            BigInt::from(0),
+        ];
+        let mut machine = IntCode::new(prog, vec![]);
+        machine.run().unwrap();
+
+        assert_eq!(machine[6], big.clone() * big);
     }
 }
@@ -0,0 +1,293 @@
+use crate::{Image, Word};
+use std::collections::BTreeMap;
+
+/// Words per page copied on write from a shared image.
+const PAGE: usize = 256;
+
+/// Dense and shared memory grow up to this many words, or the size of
+/// their image if larger. A write past that switches them to sparse cells.
+const DENSE_LIMIT: usize = 1 << 20;
+
+/// Backing store for an `IntCode` machine.
+///
+/// Reads past anything that has been written yield zero,
+/// writes anywhere succeed. Memories compare equal when every
+/// address reads the same, regardless of backend.
+///
+/// Dense and shared memory switch to sparse cells on a write far past
+/// their end, so a stray address can't allocate unbounded storage.
+#[derive(Debug, Clone)]
+pub struct Memory<W> {
+    cells: Cells<W>,
+    zero: W,
+}
+
+#[derive(Debug, Clone)]
+enum Cells<W> {
+    /// Contiguous cells, grown with zeroes when written past the end.
+    Dense(Vec<W>),
+
+    /// Only cells that have been written, for programs touching huge addresses.
+    Sparse(BTreeMap<usize, W>),
+
+    /// A shared image, with the pages that have been written copied out of it.
+    Shared {
+        image: Image<W>,
+        pages: Vec<Option<Box<[W]>>>,
+        len: usize,
+    },
+}
+
+impl<W: Word> Memory<W> {
+    /// Contiguous memory initialized with a program image.
+    pub fn dense(image: Vec<W>) -> Memory<W> {
+        let cells = Cells::Dense(image);
+        let zero = W::from(0);
+
+        Memory { cells, zero }
+    }
+
+    /// Sparse memory initialized with a program image.
+    pub fn sparse(image: Vec<W>) -> Memory<W> {
+        let cells = Cells::Sparse(image.into_iter().enumerate().collect());
+        let zero = W::from(0);
+
+        Memory { cells, zero }
+    }
+
+    /// Memory reading through to a shared program image,
+    /// copying a page of it on the first write into that page.
+    pub fn shared(image: &Image<W>) -> Memory<W> {
+        let cells = Cells::Shared {
+            len: image.len(),
+            image: image.clone(),
+            pages: Vec::new(),
+        };
+        let zero = W::from(0);
+
+        Memory { cells, zero }
+    }
+
+    /// Pages copied out of a shared image, zero for other backends.
+    pub fn copied_pages(&self) -> usize {
+        match &self.cells {
+            Cells::Shared { pages, .. } => pages.iter().filter(|page| page.is_some()).count(),
+            _ => 0,
+        }
+    }
+
+    /// The image shared memory reads through to.
+    pub(crate) fn image(&self) -> Option<&Image<W>> {
+        match &self.cells {
+            Cells::Shared { image, .. } => Some(image),
+            _ => None,
+        }
+    }
+
+    pub fn is_sparse(&self) -> bool {
+        matches!(self.cells, Cells::Sparse(_))
+    }
+
+    /// One past the highest address that is backed by storage.
+    pub fn len(&self) -> usize {
+        match &self.cells {
+            Cells::Dense(cells) => cells.len(),
+            Cells::Sparse(cells) => cells
+                .keys()
+                .next_back()
+                .map_or(0, |addr| addr.saturating_add(1)),
+            Cells::Shared { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stored cells in address order.
+    ///
+    /// Unstored addresses are omitted, but stored zeroes may be included.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (usize, &W)> + '_> {
+        match &self.cells {
+            Cells::Dense(cells) => Box::new(cells.iter().enumerate()),
+            Cells::Sparse(cells) => Box::new(cells.iter().map(|(addr, cell)| (*addr, cell))),
+            Cells::Shared { len, .. } => {
+                Box::new((0..*len).map(move |addr| (addr, self.get(addr))))
+            }
+        }
+    }
+
+    pub fn get(&self, addr: usize) -> &W {
+        let cell = match &self.cells {
+            Cells::Dense(cells) => cells.get(addr),
+            Cells::Sparse(cells) => cells.get(&addr),
+            Cells::Shared { image, pages, .. } => match pages.get(addr / PAGE) {
+                Some(Some(page)) => page.get(addr % PAGE),
+                _ => image.get(addr),
+            },
+        };
+
+        cell.unwrap_or(&self.zero)
+    }
+
+    pub fn get_mut(&mut self, addr: usize) -> &mut W {
+        if self.too_far(addr) {
+            let cells = self
+                .iter()
+                .filter(|(_, cell)| **cell != self.zero)
+                .map(|(addr, cell)| (addr, cell.clone()))
+                .collect();
+
+            self.cells = Cells::Sparse(cells);
+        }
+
+        let zero = &self.zero;
+
+        match &mut self.cells {
+            Cells::Dense(cells) => {
+                if addr >= cells.len() {
+                    cells.resize(addr + 1, zero.clone());
+                }
+
+                &mut cells[addr]
+            }
+
+            Cells::Sparse(cells) => cells.entry(addr).or_insert_with(|| zero.clone()),
+
+            Cells::Shared { image, pages, len } => {
+                let index = addr / PAGE;
+
+                if pages.len() <= index {
+                    pages.resize(index + 1, None);
+                }
+
+                *len = (*len).max(addr + 1);
+
+                let page = pages[index].get_or_insert_with(|| {
+                    let start = index * PAGE;
+
+                    (start..start + PAGE)
+                        .map(|addr| image.get(addr).unwrap_or(zero).clone())
+                        .collect()
+                });
+
+                &mut page[addr % PAGE]
+            }
+        }
+    }
+}
+
+impl<W> Memory<W> {
+    /// Whether writing `addr` would grow dense or shared memory past its limit.
+    fn too_far(&self, addr: usize) -> bool {
+        let end = match &self.cells {
+            Cells::Dense(cells) => cells.len(),
+            Cells::Sparse(_) => return false,
+            Cells::Shared { image, .. } => image.len(),
+        };
+
+        addr >= end.max(DENSE_LIMIT)
+    }
+}
+
+impl<W: Word> PartialEq for Memory<W> {
+    fn eq(&self, other: &Memory<W>) -> bool {
+        let nonzero = |(_, cell): &(usize, &W)| **cell != self.zero;
+
+        self.iter().filter(nonzero).eq(other.iter().filter(nonzero))
+    }
+}
+
+impl<W: Word> From<Vec<W>> for Memory<W> {
+    fn from(image: Vec<W>) -> Memory<W> {
+        Memory::dense(image)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dense_grows() {
+        let mut mem = Memory::dense(vec![1i64, 2]);
+
+        assert_eq!(*mem.get(10), 0);
+        assert_eq!(mem.len(), 2);
+
+        *mem.get_mut(5) = 7;
+
+        assert_eq!(mem.len(), 6);
+        assert_eq!(*mem.get(4), 0);
+        assert_eq!(*mem.get(5), 7);
+    }
+
+    #[test]
+    fn far_write_goes_sparse() {
+        let mut dense = Memory::dense(vec![1i64, 0, 2]);
+        *dense.get_mut(DENSE_LIMIT - 1) = 3;
+        assert!(!dense.is_sparse());
+
+        *dense.get_mut(1 << 60) = 4;
+        assert!(dense.is_sparse());
+        assert_eq!((*dense.get(2), *dense.get(DENSE_LIMIT - 1)), (2, 3));
+        assert_eq!(*dense.get(1 << 60), 4);
+
+        let image = Image::from(vec![5i64, 6]);
+        let mut shared = Memory::shared(&image);
+        *shared.get_mut(usize::MAX) = 7;
+        assert!(shared.is_sparse());
+        assert_eq!((*shared.get(1), *shared.get(usize::MAX)), (6, 7));
+        assert_eq!(shared.len(), usize::MAX);
+    }
+
+    #[test]
+    fn sparse_huge_address() {
+        let mut mem = Memory::sparse(vec![1i64, 2]);
+
+        *mem.get_mut(1 << 40) = 3;
+
+        assert_eq!(*mem.get(1), 2);
+        assert_eq!(*mem.get(1 << 40), 3);
+        assert_eq!(*mem.get(1 << 39), 0);
+        assert_eq!(mem.len(), (1 << 40) + 1);
+    }
+
+    #[test]
+    fn equality() {
+        let mut dense = Memory::dense(vec![1i64, 0, 2]);
+        let sparse = Memory::sparse(vec![1i64, 0, 2, 0, 0]);
+
+        assert_eq!(dense, sparse);
+
+        *dense.get_mut(8) = 0;
+        assert_eq!(dense, sparse);
+
+        *dense.get_mut(8) = 1;
+        assert_ne!(dense, sparse);
+    }
+
+    #[test]
+    fn shared_copies_on_write() {
+        let image = Image::from((0..1000i64).collect::<Vec<_>>());
+        let mut a = Memory::shared(&image);
+        let b = Memory::shared(&image);
+
+        *a.get_mut(300) = -1;
+        *a.get_mut(301) = -2;
+
+        assert_eq!(a.copied_pages(), 1);
+        assert_eq!((*a.get(299), *a.get(300), *a.get(302)), (299, -1, 302));
+        assert_eq!(*b.get(300), 300);
+        assert_eq!(image[300], 300);
+
+        *a.get_mut(5000) = 7;
+
+        assert_eq!(a.copied_pages(), 2);
+        assert_eq!(a.len(), 5001);
+        assert_eq!(*a.get(4999), 0);
+        assert_eq!(*a.get(5000), 7);
+        assert_eq!(b.len(), 1000);
+        assert_eq!(b, Memory::dense((0..1000).collect()));
+    }
+}
@@ -0,0 +1,509 @@
+//! Machines exchanging packets over a network.
+//!
+//! A `Network` boots machines that each read their address as first input,
+//! then send packets as three outputs: destination address, X and Y. Packets
+//! are queued as input of the destination machine, which reads X and Y in
+//! turn, or -1 when nothing is queued for it. Packets sent to `NAT` are kept
+//! by the network, and the last one is replayed to address 0 whenever the
+//! whole network sits idle.
+
+use crate::sched::{run_slice, State, SLICE};
+use crate::{Image, IntCode, MachineError, Word};
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Address of the NAT, which wakes the network when it goes idle.
+pub const NAT: usize = 255;
+
+/// Empty reads in a row after which a machine counts as idle.
+const IDLE_READS: u32 = 2;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Packet<W> {
+    pub dest: usize,
+    pub x: W,
+    pub y: W,
+}
+
+/// Something that happened on the network.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Event<W> {
+    /// A machine sent a packet, possibly to the NAT.
+    Sent { from: usize, packet: Packet<W> },
+
+    /// The network was idle and the NAT sent this packet to address 0.
+    Wake(Packet<W>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum NetError<W> {
+    /// A machine faulted, it is not run again.
+    Fault { addr: usize, err: MachineError<W> },
+
+    /// A machine sent a packet to a destination that is neither a machine
+    /// nor the NAT. The packet is dropped.
+    Unroutable { addr: usize, dest: W },
+
+    /// A packet given to `Network::send` has no machine at its destination.
+    NoMachine(usize),
+
+    /// A network was made with this many machines, so some would have the
+    /// NAT's address or one beyond it.
+    TooLarge(usize),
+}
+
+impl<W: Word> Display for NetError<W> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            NetError::Fault { addr, err } => write!(fmt, "machine {} faulted at {}", addr, err),
+            NetError::Unroutable { addr, dest } => {
+                write!(fmt, "machine {} sent a packet to {}", addr, dest)
+            }
+            NetError::NoMachine(dest) => write!(fmt, "no machine at address {}", dest),
+            NetError::TooLarge(size) => write!(
+                fmt,
+                "{} machines do not fit below the NAT's address {}",
+                size, NAT
+            ),
+        }
+    }
+}
+
+struct Node<W> {
+    machine: IntCode<W>,
+    state: State<W>,
+
+    /// Packets waiting to be read, as X and Y.
+    queue: VecDeque<(W, W)>,
+
+    /// Outputs of a packet still being sent.
+    sending: Vec<W>,
+
+    /// Reads of an empty queue since the machine last received or sent anything.
+    empty_reads: u32,
+}
+
+/// Machines connected by packet queues, run round-robin on one thread.
+///
+/// Iterating over a network runs it and yields what happens on it. Iteration
+/// ends when every machine has stopped, or when the network is idle and the
+/// NAT has nothing to wake it with.
+pub struct Network<W = i64> {
+    nodes: Vec<Node<W>>,
+    nat: Option<Packet<W>>,
+    events: VecDeque<Result<Event<W>, NetError<W>>>,
+}
+
+impl<W: Word> Network<W> {
+    /// Boots `size` machines from `image`, with addresses 0 to `size - 1`.
+    ///
+    /// Fails if `size` is over `NAT`, as addresses from the NAT's on are not
+    /// machines.
+    pub fn boot(image: &Image<W>, size: usize) -> Result<Network<W>, NetError<W>> {
+        if size > NAT {
+            return Err(NetError::TooLarge(size));
+        }
+
+        Network::new((0..size).map(|_| IntCode::new(image, vec![])).collect())
+    }
+
+    /// Connects `machines`, giving each its index as address.
+    ///
+    /// Fails if there are more than `NAT` of them.
+    pub fn new(machines: Vec<IntCode<W>>) -> Result<Network<W>, NetError<W>> {
+        let size = machines.len();
+        if size > NAT {
+            return Err(NetError::TooLarge(size));
+        }
+
+        let nodes = machines
+            .into_iter()
+            .enumerate()
+            .map(|(addr, mut machine)| {
+                machine.push_input(W::from_addr(addr).ok_or(NetError::TooLarge(size))?);
+
+                Ok(Node {
+                    machine,
+                    state: State::Runnable,
+                    queue: VecDeque::new(),
+                    sending: Vec::new(),
+                    empty_reads: 0,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Network {
+            nodes,
+            nat: None,
+            events: VecDeque::new(),
+        })
+    }
+
+    /// Queues a packet as if a machine had sent it.
+    pub fn send(&mut self, packet: Packet<W>) -> Result<(), NetError<W>> {
+        if packet.dest != NAT && packet.dest >= self.nodes.len() {
+            return Err(NetError::NoMachine(packet.dest));
+        }
+
+        self.deliver(packet);
+        Ok(())
+    }
+
+    /// Queues a packet whose destination is known to exist.
+    fn deliver(&mut self, packet: Packet<W>) {
+        match packet.dest {
+            NAT => self.nat = Some(packet),
+            dest => {
+                let node = &mut self.nodes[dest];
+                node.queue.push_back((packet.x, packet.y));
+                node.empty_reads = 0;
+            }
+        }
+    }
+
+    /// Whether no packets are queued and every running machine keeps
+    /// finding its queue empty, and is waiting on it rather than computing.
+    pub fn is_idle(&self) -> bool {
+        self.nodes
+            .iter()
+            .filter(|node| node.state.is_running())
+            .all(|node| {
+                node.state == State::Blocked
+                    && node.queue.is_empty()
+                    && node.sending.is_empty()
+                    && node.empty_reads >= IDLE_READS
+            })
+    }
+
+    /// Runs each machine for a turn, returning whether any of them ran.
+    fn round(&mut self) -> bool {
+        let mut ran = false;
+
+        for addr in 0..self.nodes.len() {
+            ran |= self.turn(addr);
+        }
+
+        ran
+    }
+
+    /// Runs one machine until it reads an empty queue twice, stops or its
+    /// slice ends. Returns whether it was running.
+    fn turn(&mut self, addr: usize) -> bool {
+        if !self.nodes[addr].state.is_running() {
+            return false;
+        }
+
+        let mut steps = 0;
+        let mut polled = false;
+
+        let state = loop {
+            let mut outputs = Vec::new();
+            let node = &mut self.nodes[addr];
+            let (state, ran) = run_slice(&mut node.machine, SLICE - steps, |value| {
+                outputs.push(value)
+            });
+            steps += ran;
+
+            // routed before the machine reads, so it can send to itself
+            for value in outputs {
+                let node = &mut self.nodes[addr];
+                node.empty_reads = 0;
+                node.sending.push(value);
+
+                if node.sending.len() == 3 {
+                    self.route(addr);
+                }
+            }
+
+            let node = &mut self.nodes[addr];
+            match state {
+                State::Blocked => match node.queue.pop_front() {
+                    Some((x, y)) => {
+                        node.machine.push_input(x);
+                        node.machine.push_input(y);
+                        node.empty_reads = 0;
+                    }
+
+                    // give other machines a chance to send something
+                    None if polled => break State::Blocked,
+
+                    None => {
+                        node.machine.push_input(W::from(-1));
+                        node.empty_reads += 1;
+                        polled = true;
+                    }
+                },
+
+                State::Faulted(err) => {
+                    self.events.push_back(Err(NetError::Fault {
+                        addr,
+                        err: err.clone(),
+                    }));
+                    break State::Faulted(err);
+                }
+
+                state => break state,
+            }
+        };
+
+        self.nodes[addr].state = state;
+        true
+    }
+
+    /// Routes the packet machine `addr` has just finished sending.
+    fn route(&mut self, addr: usize) {
+        let mut words = self.nodes[addr].sending.drain(..);
+        let (dest, x, y) = match (words.next(), words.next(), words.next()) {
+            (Some(dest), Some(x), Some(y)) => (dest, x, y),
+            _ => unreachable!("packets are three words"),
+        };
+        drop(words);
+
+        let event = match dest.to_addr() {
+            Some(to) if to == NAT || to < self.nodes.len() => {
+                let packet = Packet { dest: to, x, y };
+                self.deliver(packet.clone());
+
+                Ok(Event::Sent { from: addr, packet })
+            }
+
+            _ => Err(NetError::Unroutable { addr, dest }),
+        };
+
+        self.events.push_back(event);
+    }
+
+    /// Replays the NAT's last packet to address 0, if it has one.
+    fn wake(&mut self) -> bool {
+        let packet = match &self.nat {
+            Some(packet) => Packet {
+                dest: 0,
+                ..packet.clone()
+            },
+            None => return false,
+        };
+
+        match self.nodes.first() {
+            Some(node) if node.state.is_running() => {}
+            _ => return false,
+        }
+
+        self.deliver(packet.clone());
+        self.events.push_back(Ok(Event::Wake(packet)));
+
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn state(&self, addr: usize) -> &State<W> {
+        &self.nodes[addr].state
+    }
+
+    pub fn machine(&self, addr: usize) -> &IntCode<W> {
+        &self.nodes[addr].machine
+    }
+
+    /// The last packet sent to the NAT.
+    pub fn nat(&self) -> Option<&Packet<W>> {
+        self.nat.as_ref()
+    }
+
+    /// Packets queued for machine `addr`, as X and Y.
+    pub fn queued(&self, addr: usize) -> impl Iterator<Item = &(W, W)> {
+        self.nodes[addr].queue.iter()
+    }
+}
+
+impl<W: Word> Iterator for Network<W> {
+    type Item = Result<Event<W>, NetError<W>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(event);
+            }
+
+            if self.is_idle() && !self.wake() {
+                return None;
+            }
+
+            if self.events.is_empty() && !self.round() {
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::asm::assemble;
+
+    /// Machines forward packets to the next address, adding X to Y. Machine
+    /// 0 starts off by sending `x`, `y` and the last sends to the NAT.
+    fn ring(size: usize, x: i64, y: i64) -> Network {
+        let src = format!(
+            "
+                    IN   %addr
+                    JT   %addr, loop
+                    OUT  1
+                    OUT  {x}
+                    OUT  {y}
+            loop:   IN   %x
+                    EQ   %x, -1, %t
+                    JT   %t, loop
+                    IN   %y
+                    ADD  %addr, 1, %dest
+                    EQ   %dest, {size}, %t
+                    JF   %t, send
+                    ADD  255, 0, %dest
+            send:   OUT  %dest
+                    OUT  %x
+                    ADD  %y, %x, %y
+                    OUT  %y
+                    JT   1, loop
+                    QT
+            addr:   .data 0
+            x:      .data 0
+            y:      .data 0
+            dest:   .data 0
+            t:      .data 0
+            ",
+            x = x,
+            y = y,
+            size = size,
+        );
+
+        Network::boot(&Image::from(assemble::<i64>(&src).unwrap()), size).unwrap()
+    }
+
+    fn sent(from: usize, dest: usize, x: i64, y: i64) -> Event<i64> {
+        Event::Sent {
+            from,
+            packet: Packet { dest, x, y },
+        }
+    }
+
+    #[test]
+    fn routes_and_wakes() {
+        let mut net = ring(3, 1, 10);
+        let events: Vec<_> = net.by_ref().take(5).map(Result::unwrap).collect();
+
+        assert_eq!(
+            events,
+            vec![
+                sent(0, 1, 1, 10),
+                sent(1, 2, 1, 11),
+                sent(2, NAT, 1, 12),
+                Event::Wake(Packet {
+                    dest: 0,
+                    x: 1,
+                    y: 12
+                }),
+                sent(0, 1, 1, 13),
+            ]
+        );
+
+        // the round that sent 13 also carried it on to the NAT
+        assert_eq!(net.nat().map(|p| p.y), Some(15));
+        assert!(!net.is_idle());
+        assert_eq!(net.state(2), &State::Blocked);
+    }
+
+    #[test]
+    fn repeated_wake() {
+        // with X of 0, the NAT keeps replaying the same Y
+        let mut last = None;
+        let mut repeat = None;
+
+        for event in ring(50, 0, 7).take(1_000) {
+            if let Event::Wake(packet) = event.unwrap() {
+                if last == Some(packet.y) {
+                    repeat = Some(packet.y);
+                    break;
+                }
+
+                last = Some(packet.y);
+            }
+        }
+
+        assert_eq!(repeat, Some(7));
+    }
+
+    #[test]
+    fn stops_and_errors() {
+        // idle with nothing sent to the NAT
+        let poll = Image::from(vec![3, 5, 1105, 1, 0, 0]);
+        let mut net: Network = Network::boot(&poll, 3).unwrap();
+        assert_eq!(net.next(), None);
+        assert!(net.nat().is_none());
+        assert!(net.is_idle());
+
+        let packet = |dest| Packet { dest, x: 1, y: 2 };
+        assert_eq!(net.send(packet(3)), Err(NetError::NoMachine(3)));
+        assert_eq!(net.send(packet(2)), Ok(()));
+        assert_eq!(net.queued(2).collect::<Vec<_>>(), vec![&(1, 2)]);
+        assert!(!net.is_idle());
+
+        // sends to 9, then faults
+        let mut net = Network::new(vec![IntCode::new(
+            vec![3, 9, 104, 9, 104, 1, 104, 2, 42],
+            vec![],
+        )])
+        .unwrap();
+
+        assert_eq!(
+            net.next(),
+            Some(Err(NetError::Unroutable { addr: 0, dest: 9 }))
+        );
+        assert_eq!(
+            net.next().unwrap().unwrap_err().to_string(),
+            "machine 0 faulted at 0x0008 (42): unrecognized opcode"
+        );
+        assert_eq!(net.next(), None);
+
+        assert!(Network::boot(&poll, NAT).is_ok());
+        let err = Network::boot(&poll, NAT + 1).err().unwrap();
+        assert_eq!(err, NetError::TooLarge(256));
+        assert_eq!(
+            err.to_string(),
+            "256 machines do not fit below the NAT's address 255"
+        );
+    }
+
+    #[test]
+    fn busy_is_not_idle() {
+        // on its second empty read, computes for a few slices and sends to the NAT
+        let src = "
+                    IN   %x
+            loop:   IN   %x
+                    EQ   %x, -1, %t
+                    JF   %t, loop
+                    ADD  %n, 1, %n
+                    EQ   %n, 2, %t
+                    JF   %t, loop
+            spin:   ADD  %k, 1, %k
+                    LT   %k, 3000, %t
+                    JT   %t, spin
+                    OUT  255
+                    OUT  1
+                    OUT  2
+                    JT   1, loop
+            x:      .data 0
+            t:      .data 0
+            n:      .data 0
+            k:      .data 0
+        ";
+        let image = Image::from(assemble::<i64>(src).unwrap());
+        let mut net = Network::boot(&image, 1).unwrap();
+
+        assert_eq!(net.next(), Some(Ok(sent(0, NAT, 1, 2))));
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{Memory, OpCode, Word};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+/// Execution counts gathered by `IntCode::record_profile`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    instructions: u64,
+
+    /// Executions per instruction address.
+    counts: BTreeMap<usize, u64>,
+    opcodes: BTreeMap<&'static str, u64>,
+
+    reads: BTreeMap<usize, u64>,
+    writes: BTreeMap<usize, u64>,
+}
+
+impl Profile {
+    pub(crate) fn execute<W: Word>(&mut self, ip: usize, op: &OpCode<W>) {
+        self.instructions += 1;
+        *self.counts.entry(ip).or_insert(0) += 1;
+        *self.opcodes.entry(op.mnemonic()).or_insert(0) += 1;
+    }
+
+    pub(crate) fn read(&mut self, addr: usize) {
+        *self.reads.entry(addr).or_insert(0) += 1;
+    }
+
+    pub(crate) fn write(&mut self, addr: usize) {
+        *self.writes.entry(addr).or_insert(0) += 1;
+    }
+
+    /// Total instructions executed.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Times the instruction at `addr` was executed.
+    pub fn count(&self, addr: usize) -> u64 {
+        self.counts.get(&addr).copied().unwrap_or(0)
+    }
+
+    /// Executions per opcode mnemonic, e.g. `("ADD", 12)`.
+    pub fn opcodes(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        self.opcodes.iter().map(|(name, count)| (*name, *count))
+    }
+
+    /// Times instructions read the cell at `addr` as an operand.
+    pub fn reads(&self, addr: usize) -> u64 {
+        self.reads.get(&addr).copied().unwrap_or(0)
+    }
+
+    /// Times instructions wrote the cell at `addr`.
+    pub fn writes(&self, addr: usize) -> u64 {
+        self.writes.get(&addr).copied().unwrap_or(0)
+    }
+
+    pub fn total_reads(&self) -> u64 {
+        self.reads.values().sum()
+    }
+
+    pub fn total_writes(&self) -> u64 {
+        self.writes.values().sum()
+    }
+
+    /// Executed instruction addresses with their counts, most executed first.
+    pub fn hot_spots(&self) -> Vec<(usize, u64)> {
+        let mut spots: Vec<(usize, u64)> = self
+            .counts
+            .iter()
+            .map(|(addr, count)| (*addr, *count))
+            .collect();
+
+        spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        spots
+    }
+
+    /// A summary listing the `top` hottest instructions and the `top` most
+    /// accessed cells. Instructions are disassembled from `memory`, usually
+    /// the program image, as self modifying code may have changed them since.
+    pub fn report<W: Word>(&self, memory: &Memory<W>, top: usize) -> String {
+        let percent = |count: u64| match self.instructions {
+            0 => 0.0,
+            total => count as f64 * 100.0 / total as f64,
+        };
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{} instructions, {} reads, {} writes",
+            self.instructions,
+            self.total_reads(),
+            self.total_writes()
+        );
+
+        let mut opcodes: Vec<(&str, u64)> = self.opcodes().collect();
+        opcodes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        let _ = writeln!(out, "\n{:<8}{:>10}{:>8}", "opcode", "count", "%");
+        for (name, count) in opcodes {
+            let _ = writeln!(out, "{:<8}{:>10}{:>8.1}", name, count, percent(count));
+        }
+
+        let _ = writeln!(out, "\n{:<8}{:>10}{:>8}", "addr", "count", "%");
+        for (addr, count) in self.hot_spots().into_iter().take(top) {
+            let op = match OpCode::decode(memory, addr) {
+                Ok(op) => op.to_string(),
+                Err(_) => format!(".data {}", memory.get(addr)),
+            };
+
+            let _ = writeln!(
+                out,
+                "0x{:04x}  {:>10}{:>8.1}  {}",
+                addr,
+                count,
+                percent(count),
+                op
+            );
+        }
+
+        let addrs: BTreeSet<usize> = self
+            .reads
+            .keys()
+            .chain(self.writes.keys())
+            .copied()
+            .collect();
+        let mut cells: Vec<(usize, u64, u64)> = addrs
+            .into_iter()
+            .map(|addr| (addr, self.reads(addr), self.writes(addr)))
+            .collect();
+        cells.sort_by(|a, b| (b.1 + b.2).cmp(&(a.1 + a.2)).then(a.0.cmp(&b.0)));
+
+        let _ = writeln!(out, "\n{:<8}{:>10}{:>8}", "cell", "reads", "writes");
+        for (addr, reads, writes) in cells.into_iter().take(top) {
+            let _ = writeln!(out, "%0x{:04x} {:>10}{:>8}", addr, reads, writes);
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::DOUBLER;
+    use crate::{IntCode, Memory};
+
+    #[test]
+    fn counts() {
+        let mut machine = IntCode::new(DOUBLER.to_vec(), vec![1, 2, 3, 0]);
+        machine.record_profile(true);
+        machine.run().unwrap();
+
+        let profile = machine.profile().unwrap();
+        assert_eq!(profile.instructions(), 18);
+        assert_eq!(profile.count(0), 4);
+        assert_eq!(profile.count(5), 3);
+        assert_eq!(profile.count(14), 1);
+        assert_eq!(profile.reads(100), 10);
+        assert_eq!(profile.writes(100), 7);
+        assert_eq!(
+            profile.opcodes().find(|(name, _)| *name == "JT"),
+            Some(("JT", 3))
+        );
+    }
+
+    #[test]
+    fn far_jump() {
+        let far: i64 = 1 << 40;
+        let mut machine = IntCode::new(vec![1101, 99, 0, far, 1105, 1, far], vec![]);
+        machine.record_profile(true);
+
+        assert_eq!(machine.run(), Ok(vec![]));
+
+        let profile = machine.profile().unwrap();
+        assert_eq!(profile.count(far as usize), 1);
+        assert_eq!(profile.hot_spots(), vec![(0, 1), (4, 1), (far as usize, 1)]);
+    }
+
+    #[test]
+    fn report() {
+        let prog = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let mut machine: IntCode<i64> = IntCode::new(prog.clone(), vec![]);
+        machine.record_profile(true);
+        machine.run().unwrap();
+
+        let image = Memory::from(prog);
+        let report = machine.profile().unwrap().report(&image, 2);
+
+        assert_eq!(
+            report,
+            "3 instructions, 4 reads, 2 writes\n\
+             \n\
+             opcode       count       %\n\
+             ADD              1    33.3\n\
+             MULT             1    33.3\n\
+             QT               1    33.3\n\
+             \n\
+             addr         count       %\n\
+             0x0000           1    33.3  ADD  %0x0009, %0x000a, %0x0003\n\
+             0x0004           1    33.3  MULT %0x0003, %0x000b, %0x0000\n\
+             \n\
+             cell         reads  writes\n\
+             %0x0003          1       1\n\
+             %0x0000          0       1\n"
+        );
+    }
+}
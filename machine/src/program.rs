@@ -0,0 +1,143 @@
+use crate::{Memory, Word};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A program image in the comma separated format puzzles are given in.
+///
+/// Whitespace and newlines around tokens are ignored,
+/// as is a single trailing comma.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program<W = i64> {
+    words: Vec<W>,
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    /// The source contained no tokens.
+    Empty,
+
+    /// The token at `index` is not a valid word.
+    Token { index: usize, token: String },
+
+    /// The source could not be read.
+    Io(io::Error),
+}
+
+impl<W: Word> Program<W> {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Program<W>, ParseError> {
+        std::fs::read_to_string(path)
+            .map_err(ParseError::Io)?
+            .parse()
+    }
+
+    pub fn into_vec(self) -> Vec<W> {
+        self.words
+    }
+}
+
+impl<W: Word> FromStr for Program<W> {
+    type Err = ParseError;
+
+    fn from_str(src: &str) -> Result<Program<W>, ParseError> {
+        let src = src.trim();
+        let src = src.strip_suffix(',').unwrap_or(src);
+
+        if src.is_empty() {
+            return Err(ParseError::Empty);
+        }
+
+        let mut words = Vec::new();
+        for (index, token) in src.split(',').enumerate() {
+            let token = token.trim();
+
+            match token.parse() {
+                Ok(word) => words.push(word),
+                Err(_) => {
+                    let token = token.to_string();
+                    return Err(ParseError::Token { index, token });
+                }
+            }
+        }
+
+        Ok(Program { words })
+    }
+}
+
+impl<W> Deref for Program<W> {
+    type Target = [W];
+
+    fn deref(&self) -> &[W] {
+        &self.words
+    }
+}
+
+impl<W> From<Vec<W>> for Program<W> {
+    fn from(words: Vec<W>) -> Program<W> {
+        Program { words }
+    }
+}
+
+impl<W: Word> From<Program<W>> for Memory<W> {
+    fn from(program: Program<W>) -> Memory<W> {
+        Memory::dense(program.words)
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ParseError::Empty => write!(fmt, "program is empty"),
+            ParseError::Token { index, token } => {
+                write!(fmt, "invalid word {:?} at token {}", token, index)
+            }
+            ParseError::Io(err) => write!(fmt, "unable to read program: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn whitespace() {
+        let prog: Program<i64> = " 1, 2,\n3 ,-4\n".parse().unwrap();
+
+        assert_eq!(prog.into_vec(), vec![1, 2, 3, -4]);
+    }
+
+    #[test]
+    fn trailing_comma() {
+        let prog: Program<i64> = "99,\n".parse().unwrap();
+
+        assert_eq!(&prog[..], &[99]);
+    }
+
+    #[test]
+    fn empty() {
+        let err = "  \n".parse::<Program<i64>>().unwrap_err();
+
+        assert!(matches!(err, ParseError::Empty));
+    }
+
+    #[test]
+    fn bad_token() {
+        let err = "1,2,,4".parse::<Program<i64>>().unwrap_err();
+
+        match err {
+            ParseError::Token { index, token } => {
+                assert_eq!(index, 2);
+                assert_eq!(token, "");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+
+        let err = "1, 2x".parse::<Program<i64>>().unwrap_err();
+        assert_eq!(err.to_string(), "invalid word \"2x\" at token 1");
+    }
+}
@@ -0,0 +1,348 @@
+//! Running many machines on one thread.
+//!
+//! A `Scheduler` owns a set of machines and runs them round-robin in the
+//! order they were added, each for at most a slice of instructions per turn.
+//! Outputs of a machine connected with `Scheduler::connect` are queued as
+//! input of another, so networks such as amplifier feedback loops run
+//! deterministically without threads.
+
+use crate::{Halt, IntCode, MachineError, Word};
+use std::fmt::{Display, Formatter, Result as FmtResult, Write};
+
+/// Instructions a machine runs per turn unless set with `Scheduler::set_slice`.
+pub(crate) const SLICE: u64 = 1_000;
+
+/// Where a machine stands after its last turn.
+#[derive(Debug, PartialEq, Clone)]
+pub enum State<W> {
+    /// Ready to run.
+    Runnable,
+
+    /// Waiting for input, it runs again once some is queued.
+    Blocked,
+
+    /// Executed a quit instruction.
+    Halted,
+
+    /// Stopped for another reason, e.g. at a breakpoint or its budget ran out.
+    Stopped(Halt<W>),
+
+    /// Raised a fault and is not given another turn.
+    Faulted(MachineError<W>),
+}
+
+impl<W> State<W> {
+    /// Whether the machine can still be given a turn.
+    pub(crate) fn is_running(&self) -> bool {
+        matches!(self, State::Runnable | State::Blocked)
+    }
+}
+
+impl<W: Word> Display for State<W> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            State::Runnable => write!(fmt, "runnable"),
+            State::Blocked => write!(fmt, "blocked on input"),
+            State::Halted => write!(fmt, "halted"),
+            State::Stopped(halt) => write!(fmt, "stopped: {:?}", halt),
+            State::Faulted(err) => write!(fmt, "faulted at {}", err),
+        }
+    }
+}
+
+struct Task<W> {
+    machine: IntCode<W>,
+    state: State<W>,
+
+    /// Machine the outputs are queued to, kept in `outputs` otherwise.
+    route: Option<usize>,
+    outputs: Vec<W>,
+    last: Option<W>,
+
+    steps: u64,
+}
+
+/// Round-robin scheduler over machines connected by input queues.
+pub struct Scheduler<W = i64> {
+    tasks: Vec<Task<W>>,
+    slice: u64,
+}
+
+impl<W: Word> Scheduler<W> {
+    pub fn new() -> Scheduler<W> {
+        Scheduler {
+            tasks: Vec::new(),
+            slice: SLICE,
+        }
+    }
+
+    /// Adds a machine, returning the id it is referred to by.
+    pub fn add(&mut self, machine: IntCode<W>) -> usize {
+        let state = if machine.halted() {
+            State::Halted
+        } else {
+            State::Runnable
+        };
+
+        self.tasks.push(Task {
+            machine,
+            state,
+            route: None,
+            outputs: Vec::new(),
+            last: None,
+            steps: 0,
+        });
+
+        self.tasks.len() - 1
+    }
+
+    /// Queues every output of `from` as input of `to`,
+    /// replacing any connection `from` already had.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        assert!(to < self.tasks.len(), "no machine {}", to);
+        self.tasks[from].route = Some(to);
+    }
+
+    /// Limits how many instructions a machine runs before the next gets a turn.
+    pub fn set_slice(&mut self, steps: u64) {
+        self.slice = steps.max(1);
+    }
+
+    pub fn push_input(&mut self, id: usize, value: W) {
+        self.tasks[id].machine.push_input(value);
+    }
+
+    /// Runs machines in turn until every one has halted, stopped or is
+    /// blocked on input nobody will send. Returns whether all of them halted.
+    pub fn run(&mut self) -> bool {
+        loop {
+            let mut progress = false;
+
+            for id in 0..self.tasks.len() {
+                progress |= self.turn(id);
+            }
+
+            if !progress {
+                return self.tasks.iter().all(|task| task.state == State::Halted);
+            }
+        }
+    }
+
+    /// Runs one machine for a slice, returning whether it executed anything.
+    fn turn(&mut self, id: usize) -> bool {
+        if !self.tasks[id].state.is_running() {
+            return false;
+        }
+
+        let mut outputs = Vec::new();
+        let task = &mut self.tasks[id];
+        let (state, steps) = run_slice(&mut task.machine, self.slice, |value| outputs.push(value));
+
+        task.state = state;
+        task.steps += steps;
+
+        for value in outputs {
+            self.deliver(id, value);
+        }
+
+        steps > 0
+    }
+
+    fn deliver(&mut self, id: usize, value: W) {
+        let task = &mut self.tasks[id];
+        task.last = Some(value.clone());
+
+        match task.route {
+            Some(to) => self.tasks[to].machine.push_input(value),
+            None => task.outputs.push(value),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub fn state(&self, id: usize) -> &State<W> {
+        &self.tasks[id].state
+    }
+
+    pub fn machine(&self, id: usize) -> &IntCode<W> {
+        &self.tasks[id].machine
+    }
+
+    /// Outputs of a machine that is not connected to another.
+    pub fn outputs(&self, id: usize) -> &[W] {
+        &self.tasks[id].outputs
+    }
+
+    /// The most recent output of a machine, connected or not.
+    pub fn last_output(&self, id: usize) -> Option<&W> {
+        self.tasks[id].last.as_ref()
+    }
+
+    /// Instructions a machine has executed under the scheduler.
+    pub fn steps(&self, id: usize) -> u64 {
+        self.tasks[id].steps
+    }
+
+    /// One line per machine with its state, steps and last output.
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+
+        for (id, task) in self.tasks.iter().enumerate() {
+            let _ = write!(out, "{}: {}, {} steps", id, task.state, task.steps);
+
+            if let Some(last) = &task.last {
+                let _ = write!(out, ", last output {}", last);
+            }
+
+            if let Some(to) = task.route {
+                let _ = write!(out, " -> {}", to);
+            }
+
+            out.push('\n');
+        }
+
+        out
+    }
+
+    pub fn into_machines(self) -> Vec<IntCode<W>> {
+        self.tasks.into_iter().map(|task| task.machine).collect()
+    }
+}
+
+impl<W: Word> Default for Scheduler<W> {
+    fn default() -> Scheduler<W> {
+        Scheduler::new()
+    }
+}
+
+/// Steps `machine` until it needs input, halts otherwise or has run `slice`
+/// instructions, handing each output to `output`. Breakpoints stop it as
+/// they do `IntCode::exec`. Returns the state it is left in, `Runnable` if
+/// the slice ran out, and the instructions executed.
+pub(crate) fn run_slice<W: Word>(
+    machine: &mut IntCode<W>,
+    slice: u64,
+    mut output: impl FnMut(W),
+) -> (State<W>, u64) {
+    let mut steps = 0;
+
+    while steps < slice {
+        if machine.break_here() {
+            return (State::Stopped(Halt::Breakpoint), steps);
+        }
+
+        match machine.step() {
+            Ok(None) => steps += 1,
+
+            Ok(Some(Halt::Output(value))) => {
+                steps += 1;
+                output(value);
+            }
+
+            Ok(Some(Halt::Input)) => return (State::Blocked, steps),
+            Ok(Some(Halt::Quit)) => return (State::Halted, steps),
+            Ok(Some(halt)) => return (State::Stopped(halt), steps),
+            Err(err) => return (State::Faulted(err), steps),
+        }
+    }
+
+    (State::Runnable, steps)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{ECHO, FEEDBACK};
+
+    /// Runs day 7's amplifier loop, returning the last signal and the
+    /// steps each amplifier took.
+    fn feedback_loop(slice: u64) -> (Option<i64>, Vec<u64>) {
+        let mut sched = Scheduler::new();
+        sched.set_slice(slice);
+
+        let amps: Vec<usize> = [9, 8, 7, 6, 5]
+            .iter()
+            .map(|phase| sched.add(IntCode::new(FEEDBACK.to_vec(), vec![*phase])))
+            .collect();
+
+        for (i, amp) in amps.iter().enumerate() {
+            sched.connect(*amp, amps[(i + 1) % amps.len()]);
+        }
+        sched.push_input(amps[0], 0);
+
+        assert!(sched.run());
+        assert!(sched.outputs(amps[4]).is_empty());
+
+        let steps = amps.iter().map(|amp| sched.steps(*amp)).collect();
+        (sched.last_output(amps[4]).copied(), steps)
+    }
+
+    #[test]
+    fn slices_do_not_change_results() {
+        let (signal, steps) = feedback_loop(SLICE);
+        assert_eq!(signal, Some(139629729));
+
+        for slice in [1, 3, 20] {
+            assert_eq!(feedback_loop(slice), (signal, steps.clone()));
+        }
+    }
+
+    #[test]
+    fn blocked_and_resumed() {
+        let mut sched = Scheduler::new();
+        let a = sched.add(IntCode::new(ECHO.to_vec(), vec![1]));
+        let b = sched.add(IntCode::new(ECHO.to_vec(), vec![]));
+        sched.connect(a, b);
+
+        assert!(!sched.run());
+        assert_eq!(sched.state(a), &State::Blocked);
+        assert_eq!(sched.outputs(b), &[1]);
+
+        sched.push_input(a, 2);
+        sched.run();
+        assert_eq!(sched.outputs(b), &[1, 2]);
+        assert_eq!(
+            sched.report(),
+            "0: blocked on input, 6 steps, last output 2 -> 1\n\
+             1: blocked on input, 6 steps, last output 2\n"
+        );
+    }
+
+    #[test]
+    fn faults_and_slices() {
+        let mut sched = Scheduler::new();
+        let spin = sched.add(IntCode::new(vec![1105, 1, 0], vec![]));
+        let fault = sched.add(IntCode::new(vec![104, 1, 42], vec![]));
+
+        let mut budget = IntCode::new(vec![1105, 1, 0], vec![]);
+        budget.set_budget(Some(5));
+        sched.add(budget);
+
+        sched.set_slice(10);
+        sched.turn(spin);
+        sched.turn(fault);
+        assert_eq!(sched.steps(spin), 10);
+        assert!(matches!(sched.state(fault), State::Faulted(_)));
+        assert_eq!(sched.outputs(fault), &[1]);
+
+        sched.turn(2);
+        assert_eq!(sched.state(2), &State::Stopped(Halt::Budget));
+
+        let mut stops = IntCode::new(vec![1105, 1, 0], vec![]);
+        stops.set_breakpoint(0);
+        let stops = sched.add(stops);
+        sched.turn(stops);
+        assert_eq!(sched.state(stops), &State::Stopped(Halt::Breakpoint));
+        assert_eq!(sched.steps(stops), 0);
+        assert_eq!(
+            sched.state(fault).to_string(),
+            "faulted at 0x0002 (42): unrecognized opcode"
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use crate::format::{self, join, FormatError, Lines};
+use crate::{Memory, Word};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+const HEADER: &str = "intcode-snapshot 1";
+
+/// The complete execution state of an `IntCode` machine.
+///
+/// Attached devices, tracers and limits are not part of the state.
+///
+/// Snapshots are saved as line oriented text:
+///
+/// ```text
+/// intcode-snapshot 1
+/// ip 4
+/// rb 0
+/// halted false
+/// input 7,8
+/// memory dense 1,9,10,70,2,3,11,0,99,30,40,50
+/// ```
+///
+/// Sparse memory is written as `memory sparse addr:value,...`.
+#[derive(Debug, Clone)]
+pub struct Snapshot<W = i64> {
+    pub memory: Memory<W>,
+    pub ip: usize,
+    pub rb: W,
+
+    /// Pushed input that has not been consumed yet.
+    pub input: Vec<W>,
+    pub halted: bool,
+}
+
+impl<W: Word> Snapshot<W> {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        format::save(self, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Snapshot<W>, FormatError> {
+        format::load(path)
+    }
+}
+
+impl<W: Word> PartialEq for Snapshot<W> {
+    fn eq(&self, other: &Snapshot<W>) -> bool {
+        self.memory == other.memory
+            && self.ip == other.ip
+            && self.rb == other.rb
+            && self.input == other.input
+            && self.halted == other.halted
+    }
+}
+
+impl<W: Word> Display for Snapshot<W> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        writeln!(fmt, "{}", HEADER)?;
+        writeln!(fmt, "ip {}", self.ip)?;
+        writeln!(fmt, "rb {}", self.rb)?;
+        writeln!(fmt, "halted {}", self.halted)?;
+        writeln!(fmt, "input {}", join(&self.input))?;
+
+        if self.memory.is_sparse() {
+            let cells = self
+                .memory
+                .iter()
+                .map(|(addr, cell)| format!("{}:{}", addr, cell));
+
+            writeln!(fmt, "memory sparse {}", join(cells))
+        } else {
+            let cells = self.memory.iter().map(|(_, cell)| cell);
+
+            writeln!(fmt, "memory dense {}", join(cells))
+        }
+    }
+}
+
+impl<W: Word> FromStr for Snapshot<W> {
+    type Err = FormatError;
+
+    fn from_str(src: &str) -> Result<Snapshot<W>, FormatError> {
+        let mut lines = Lines::new(src);
+        lines.header(HEADER)?;
+
+        let ip = lines.field("ip")?;
+        let ip = lines.parse(ip)?;
+        let rb = lines.field("rb")?;
+        let rb = lines.parse(rb)?;
+        let halted = lines.field("halted")?;
+        let halted = lines.parse(halted)?;
+        let input = lines.field("input")?;
+        let input = lines.words(input)?;
+
+        let memory = lines.field("memory")?;
+        let memory = if let Some(cells) = memory.strip_prefix("dense") {
+            Memory::dense(lines.words(cells.trim_start())?)
+        } else if let Some(cells) = memory.strip_prefix("sparse") {
+            let mut memory = Memory::sparse(Vec::new());
+
+            for cell in Lines::tokens(cells.trim_start()) {
+                match cell.split_once(':') {
+                    Some((addr, value)) => {
+                        *memory.get_mut(lines.parse(addr)?) = lines.parse(value)?
+                    }
+                    None => return lines.err(format!("invalid cell {:?}", cell)),
+                }
+            }
+
+            memory
+        } else {
+            return lines.err("expected dense or sparse memory".to_string());
+        };
+
+        Ok(Snapshot {
+            memory,
+            ip,
+            rb,
+            input,
+            halted,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Halt, IntCode};
+
+    #[test]
+    fn save_restore() {
+        let prog = vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+        let mut machine: IntCode<i64> = IntCode::new(prog, vec![20]);
+
+        assert_eq!(machine.exec(), Ok(Halt::Input));
+        let snapshot = machine.snapshot();
+
+        machine.push_input(1);
+        assert_eq!(machine.run(), Ok(vec![21]));
+
+        machine.restore(&snapshot);
+        assert!(!machine.halted());
+        machine.push_input(22);
+        assert_eq!(machine.run(), Ok(vec![42]));
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let prog = vec![3, 11, 99];
+        let mut machine: IntCode<i64> = IntCode::new(prog, vec![]);
+        machine.push_input(-5);
+        machine.push_input(6);
+
+        let snapshot = machine.snapshot();
+        let text = snapshot.to_string();
+
+        assert_eq!(
+            text,
+            "intcode-snapshot 1\nip 0\nrb 0\nhalted false\ninput -5,6\nmemory dense 3,11,99\n"
+        );
+        assert_eq!(text.parse::<Snapshot<i64>>().unwrap(), snapshot);
+    }
+
+    #[test]
+    fn sparse_round_trip() {
+        let mut memory = Memory::sparse(vec![99]);
+        *memory.get_mut(1 << 32) = 7;
+
+        let snapshot = Snapshot {
+            memory,
+            ip: 0,
+            rb: 3,
+            input: vec![],
+            halted: true,
+        };
+
+        let text = snapshot.to_string();
+        assert!(text.ends_with("memory sparse 0:99,4294967296:7\n"));
+
+        let parsed: Snapshot<i64> = text.parse().unwrap();
+        assert!(parsed.memory.is_sparse());
+        assert_eq!(parsed, snapshot);
+    }
+
+    #[test]
+    fn format_error() {
+        let err = "intcode-snapshot 1\nip 0\nrb x\n"
+            .parse::<Snapshot<i64>>()
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "line 3: invalid value \"x\"");
+    }
+}
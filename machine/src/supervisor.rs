@@ -0,0 +1,552 @@
+//! Running machines on their own threads.
+//!
+//! A `Supervisor` starts one thread per machine, with input and output bound
+//! to `std::sync::mpsc` channels. Machines connected with
+//! `Supervisor::connect` feed each other directly. When a worker ends for
+//! any reason, including a fault or a panic, its channels close, so machines
+//! waiting on it stop rather than hang. Machines waiting on each other, with
+//! no `Input` handle left to feed them, all end starved as well, so
+//! `Running::join` returns unless a machine computes forever.
+
+use crate::device::{Sink, Source};
+use crate::{Halt, IntCode, MachineError, Word};
+use std::any::Any;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::mem;
+use std::sync::mpsc::{channel, Receiver, SendError, Sender, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+
+/// Why a worker ended.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Exit<W> {
+    /// The machine executed a quit instruction.
+    Halted,
+
+    /// The machine needed input that nothing is left to send.
+    Starved,
+
+    /// The machine hit a breakpoint, a watch or the end of its budget.
+    Stopped(Halt<W>),
+
+    /// The machine raised a fault.
+    Faulted(MachineError<W>),
+
+    /// The worker thread panicked with this message.
+    Panicked(String),
+}
+
+impl<W> Exit<W> {
+    /// Whether the worker faulted or panicked.
+    pub fn is_error(&self) -> bool {
+        matches!(self, Exit::Faulted(_) | Exit::Panicked(_))
+    }
+}
+
+impl<W: Word> Display for Exit<W> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Exit::Halted => write!(fmt, "halted"),
+            Exit::Starved => write!(fmt, "starved of input"),
+            Exit::Stopped(halt) => write!(fmt, "stopped: {:?}", halt),
+            Exit::Faulted(err) => write!(fmt, "faulted at {}", err),
+            Exit::Panicked(msg) => write!(fmt, "panicked: {}", msg),
+        }
+    }
+}
+
+/// A worker's final state.
+pub struct Finished<W> {
+    pub exit: Exit<W>,
+
+    /// The machine as it ended, `None` if its thread panicked.
+    pub machine: Option<IntCode<W>>,
+
+    /// Outputs of a machine not connected to another,
+    /// unless they were taken with `Supervisor::output`.
+    pub outputs: Vec<W>,
+}
+
+/// What the workers are up to, to tell when they all wait on each other.
+#[derive(Default)]
+struct Census {
+    /// Workers still running.
+    live: usize,
+
+    /// Workers waiting for input.
+    blocked: usize,
+
+    /// Values sent to a machine and not yet received.
+    pending: usize,
+
+    /// `Input` handles that could still send.
+    handles: usize,
+
+    /// Set once every live worker waits with nothing on its way.
+    deadlocked: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    census: Mutex<Census>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn census(&self) -> MutexGuard<'_, Census> {
+        self.census.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Sending end of a machine's input, counting values in flight.
+struct Link<W> {
+    sender: Sender<W>,
+    shared: Arc<Shared>,
+}
+
+impl<W> Link<W> {
+    fn send(&self, value: W) -> Result<(), SendError<W>> {
+        let mut census = self.shared.census();
+        self.sender.send(value)?;
+
+        census.pending += 1;
+        self.shared.changed.notify_all();
+
+        Ok(())
+    }
+}
+
+impl<W> Clone for Link<W> {
+    fn clone(&self) -> Link<W> {
+        Link {
+            sender: self.sender.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<W: Word> Sink<W> for Link<W> {
+    fn send(&mut self, value: W) {
+        let _ = Link::send(self, value);
+    }
+}
+
+/// Receiving end of a machine's input.
+///
+/// Blocks until a value arrives, giving `None` once every sender is gone
+/// or every live worker is waiting with nothing on its way.
+struct Inbox<W> {
+    receiver: Receiver<W>,
+    shared: Arc<Shared>,
+}
+
+impl<W: Word> Source<W> for Inbox<W> {
+    fn next(&mut self) -> Option<W> {
+        let mut census = self.shared.census();
+
+        loop {
+            match self.receiver.try_recv() {
+                Ok(value) => {
+                    census.pending -= 1;
+                    return Some(value);
+                }
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => {}
+            }
+
+            if census.deadlocked {
+                return None;
+            }
+
+            if census.blocked + 1 == census.live && census.pending == 0 && census.handles == 0 {
+                census.deadlocked = true;
+                self.shared.changed.notify_all();
+
+                return None;
+            }
+
+            census.blocked += 1;
+            census = self
+                .shared
+                .changed
+                .wait(census)
+                .unwrap_or_else(PoisonError::into_inner);
+            census.blocked -= 1;
+        }
+    }
+}
+
+/// Values left unread when a worker ends are no longer on their way, and
+/// nothing more can be sent once the receiver is gone.
+impl<W> Drop for Inbox<W> {
+    fn drop(&mut self) {
+        let mut census = self.shared.census();
+
+        let (_, closed) = channel();
+        let receiver = mem::replace(&mut self.receiver, closed);
+        census.pending -= receiver.try_iter().count();
+        drop(receiver);
+
+        self.shared.changed.notify_all();
+    }
+}
+
+/// Feeds the input of a machine, see `Supervisor::input`.
+///
+/// Machines are only starved of input once every handle is dropped.
+pub struct Input<W> {
+    link: Link<W>,
+}
+
+impl<W> Input<W> {
+    fn new(link: Link<W>) -> Input<W> {
+        link.shared.census().handles += 1;
+        Input { link }
+    }
+
+    /// Sends a value, failing once the machine's worker has ended.
+    pub fn send(&self, value: W) -> Result<(), SendError<W>> {
+        self.link.send(value)
+    }
+}
+
+impl<W> Clone for Input<W> {
+    fn clone(&self) -> Input<W> {
+        Input::new(self.link.clone())
+    }
+}
+
+impl<W> Drop for Input<W> {
+    fn drop(&mut self) {
+        self.link.shared.census().handles -= 1;
+        self.link.shared.changed.notify_all();
+    }
+}
+
+/// Counts a worker as live until its thread ends, even by panicking.
+struct Alive(Arc<Shared>);
+
+impl Drop for Alive {
+    fn drop(&mut self) {
+        self.0.census().live -= 1;
+        self.0.changed.notify_all();
+    }
+}
+
+/// Where a machine's outputs go.
+enum Route<W> {
+    Machine(Link<W>),
+    Outbox(Sender<W>),
+}
+
+impl<W: Word> Sink<W> for Route<W> {
+    fn send(&mut self, value: W) {
+        match self {
+            Route::Machine(link) => Sink::send(link, value),
+            Route::Outbox(sender) => Sink::send(sender, value),
+        }
+    }
+}
+
+struct Worker<W> {
+    machine: IntCode<W>,
+    input: Link<W>,
+    inbox: Inbox<W>,
+
+    /// Where outputs go, `outbox` if nowhere else.
+    route: Option<Route<W>>,
+    outbox: Option<Receiver<W>>,
+}
+
+/// Machines waiting to be started on their own threads.
+pub struct Supervisor<W = i64> {
+    workers: Vec<Worker<W>>,
+    shared: Arc<Shared>,
+}
+
+/// Machines running on their own threads, see `Supervisor::start`.
+pub struct Running<W = i64> {
+    handles: Vec<JoinHandle<(IntCode<W>, Exit<W>)>>,
+    outboxes: Vec<Option<Receiver<W>>>,
+}
+
+impl<W: Word> Supervisor<W> {
+    pub fn new() -> Supervisor<W> {
+        Supervisor {
+            workers: Vec::new(),
+            shared: Arc::default(),
+        }
+    }
+
+    /// Adds a machine to be run on its own thread, returning its id.
+    ///
+    /// Any input source or output sink already attached is replaced by channels.
+    pub fn add(&mut self, machine: IntCode<W>) -> usize {
+        let (sender, receiver) = channel();
+        let shared = self.shared.clone();
+
+        self.workers.push(Worker {
+            machine,
+            input: Link {
+                sender,
+                shared: shared.clone(),
+            },
+            inbox: Inbox { receiver, shared },
+            route: None,
+            outbox: None,
+        });
+
+        self.workers.len() - 1
+    }
+
+    /// Sends every output of `from` to the input of `to`. Only the latest
+    /// of `connect` and `output` for a machine takes effect.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        let input = self.workers[to].input.clone();
+        self.workers[from].route = Some(Route::Machine(input));
+    }
+
+    /// A handle feeding the input of machine `id`.
+    pub fn input(&self, id: usize) -> Input<W> {
+        Input::new(self.workers[id].input.clone())
+    }
+
+    /// A receiver for the outputs of machine `id`, to read them as they are
+    /// produced. Outputs received here are not collected in `Finished`.
+    pub fn output(&mut self, id: usize) -> Receiver<W> {
+        let (sender, receiver) = channel();
+        self.workers[id].route = Some(Route::Outbox(sender));
+
+        receiver
+    }
+
+    /// Starts every machine on its own thread.
+    pub fn start(self) -> Running<W> {
+        let mut handles = Vec::new();
+        let mut outboxes = Vec::new();
+
+        self.shared.census().live = self.workers.len();
+
+        for (id, worker) in self.workers.into_iter().enumerate() {
+            let Worker {
+                mut machine,
+                inbox,
+                route,
+                mut outbox,
+                ..
+            } = worker;
+
+            let route = route.unwrap_or_else(|| {
+                let (sender, receiver) = channel();
+                outbox = Some(receiver);
+
+                Route::Outbox(sender)
+            });
+
+            machine.input_from(inbox);
+            machine.output_to(route);
+
+            let alive = Alive(self.shared.clone());
+            let handle = thread::Builder::new()
+                .name(format!("intcode-{}", id))
+                .spawn(move || {
+                    let _alive = alive;
+                    work(machine)
+                })
+                .expect("unable to spawn machine thread");
+
+            handles.push(handle);
+            outboxes.push(outbox);
+        }
+
+        Running { handles, outboxes }
+    }
+}
+
+impl<W: Word> Default for Supervisor<W> {
+    fn default() -> Supervisor<W> {
+        Supervisor::new()
+    }
+}
+
+/// Runs a machine until it ends, then closes its channels.
+fn work<W: Word>(mut machine: IntCode<W>) -> (IntCode<W>, Exit<W>) {
+    let exit = match machine.exec() {
+        Ok(Halt::Quit) => Exit::Halted,
+        Ok(Halt::Input) => Exit::Starved,
+        Ok(halt) => Exit::Stopped(halt),
+        Err(err) => Exit::Faulted(err),
+    };
+
+    machine.disconnect();
+    (machine, exit)
+}
+
+impl<W: Word> Running<W> {
+    /// Waits for every worker to end, in the order the machines were added.
+    pub fn join(self) -> Vec<Finished<W>> {
+        self.handles
+            .into_iter()
+            .zip(self.outboxes)
+            .map(|(handle, outbox)| {
+                let (exit, machine) = match handle.join() {
+                    Ok((machine, exit)) => (exit, Some(machine)),
+                    Err(payload) => (Exit::Panicked(panic_message(payload)), None),
+                };
+
+                let outputs = outbox.map_or_else(Vec::new, |outbox| outbox.try_iter().collect());
+
+                Finished {
+                    exit,
+                    machine,
+                    outputs,
+                }
+            })
+            .collect()
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(msg) => msg.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{DOUBLER, ECHO};
+    use crate::trace::{Record, Tracer};
+
+    struct Explode;
+
+    impl Tracer<i64> for Explode {
+        fn record(&mut self, _: &Record<i64>) {
+            panic!("tracer failed");
+        }
+    }
+
+    #[test]
+    fn outputs_while_running() {
+        let mut supervisor = Supervisor::new();
+        let doubler = supervisor.add(IntCode::new(DOUBLER.to_vec(), vec![]));
+        let outputs = supervisor.output(doubler);
+        let feed = supervisor.input(doubler);
+
+        let running = supervisor.start();
+
+        // each answer arrives before the next question is asked
+        for value in 1..=3 {
+            feed.send(value).unwrap();
+            assert_eq!(outputs.recv(), Ok(value * 2));
+        }
+
+        feed.send(0).unwrap();
+        let finished = running.join();
+
+        assert_eq!(finished[doubler].exit, Exit::Halted);
+        assert!(finished[doubler].outputs.is_empty());
+        assert!(feed.send(1).is_err());
+    }
+
+    #[test]
+    fn starved_and_collected() {
+        let mut supervisor = Supervisor::new();
+        let a = supervisor.add(IntCode::new(ECHO.to_vec(), vec![1, 2]));
+        let b = supervisor.add(IntCode::new(ECHO.to_vec(), vec![]));
+        supervisor.connect(a, b);
+
+        let finished = supervisor.start().join();
+
+        assert_eq!(finished[a].exit, Exit::Starved);
+        assert_eq!(finished[b].exit, Exit::Starved);
+        assert_eq!(finished[b].outputs, vec![1, 2]);
+        assert!(finished[b].machine.is_some());
+    }
+
+    #[test]
+    fn errors_do_not_hang() {
+        let mut supervisor = Supervisor::new();
+        let fault = supervisor.add(IntCode::new(vec![104, 1, 42], vec![]));
+        let echo = supervisor.add(IntCode::new(ECHO.to_vec(), vec![]));
+        supervisor.connect(fault, echo);
+        supervisor.connect(echo, fault);
+
+        let mut panics = IntCode::new(vec![3, 0, 99], vec![]);
+        panics.trace(Explode);
+        let panics = supervisor.add(panics);
+
+        let waiting = supervisor.add(IntCode::new(ECHO.to_vec(), vec![]));
+        supervisor.connect(waiting, panics);
+        supervisor.connect(panics, waiting);
+        supervisor.input(panics).send(5).unwrap();
+
+        let finished = supervisor.start().join();
+
+        assert!(finished[fault].exit.is_error());
+        assert_eq!(
+            finished[fault].exit.to_string(),
+            "faulted at 0x0002 (42): unrecognized opcode"
+        );
+        assert_eq!(finished[echo].exit, Exit::Starved);
+
+        assert_eq!(
+            finished[panics].exit,
+            Exit::Panicked("tracer failed".to_string())
+        );
+        assert!(finished[panics].machine.is_none());
+        assert_eq!(finished[waiting].exit, Exit::Starved);
+    }
+
+    #[test]
+    fn unread_input_does_not_hang() {
+        let mut supervisor = Supervisor::new();
+        let quits = supervisor.add(IntCode::new(vec![104, 5, 99], vec![]));
+
+        // spins without reading until its budget runs out
+        let mut spins = IntCode::new(vec![1105, 1, 0], vec![]);
+        spins.set_budget(Some(100_000));
+        let spins = supervisor.add(spins);
+        supervisor.connect(quits, spins);
+
+        let c = supervisor.add(IntCode::new(ECHO.to_vec(), vec![]));
+        let d = supervisor.add(IntCode::new(ECHO.to_vec(), vec![]));
+        supervisor.connect(c, d);
+        supervisor.connect(d, c);
+
+        let finished = supervisor.start().join();
+
+        assert_eq!(finished[quits].exit, Exit::Halted);
+        assert_eq!(finished[spins].exit, Exit::Stopped(Halt::Budget));
+        assert_eq!(finished[c].exit, Exit::Starved);
+        assert_eq!(finished[d].exit, Exit::Starved);
+    }
+
+    #[test]
+    fn cycles_starve() {
+        // outputs each nonzero input less one
+        let countdown = vec![3, 14, 1006, 14, 0, 1001, 14, -1, 14, 4, 14, 1105, 1, 0, 0];
+
+        let mut supervisor = Supervisor::new();
+        let a = supervisor.add(IntCode::new(countdown.clone(), vec![]));
+        let b = supervisor.add(IntCode::new(countdown, vec![]));
+        let own = supervisor.add(IntCode::new(ECHO.to_vec(), vec![]));
+        supervisor.connect(a, b);
+        supervisor.connect(b, a);
+        supervisor.connect(own, own);
+
+        let feed = supervisor.input(a);
+        let running = supervisor.start();
+        feed.send(5).unwrap();
+        drop(feed);
+
+        // a gets 5, 3 and 1, b gets 4, 2 and 0, then both wait forever
+        for finished in running.join() {
+            assert_eq!(finished.exit, Exit::Starved);
+        }
+    }
+}
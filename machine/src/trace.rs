@@ -0,0 +1,244 @@
+//! Opt-in execution tracing.
+//!
+//! A `Tracer` attached with `IntCode::trace` receives a `Record` for every
+//! instruction the machine executes.
+
+use crate::{OpCode, Word};
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A single executed instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record<W> {
+    pub ip: usize,
+    pub op: OpCode<W>,
+
+    /// Values of the operands the instruction read, in order.
+    pub operands: Vec<W>,
+
+    /// Memory cells the instruction wrote, as (address, new value).
+    pub writes: Vec<(usize, W)>,
+}
+
+pub trait Tracer<W>: Send {
+    fn record(&mut self, record: &Record<W>);
+}
+
+impl<W: Word> Display for Record<W> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        write!(fmt, "0x{:04x}: {}", self.ip, self.op)?;
+
+        if !self.operands.is_empty() {
+            let operands: Vec<String> = self.operands.iter().map(W::to_string).collect();
+            write!(fmt, " ({})", operands.join(", "))?;
+        }
+
+        for (addr, value) in &self.writes {
+            write!(fmt, " [%0x{:04x} <- {}]", addr, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes each record as a line of text to stderr.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stderr;
+
+impl<W: Word> Tracer<W> for Stderr {
+    fn record(&mut self, record: &Record<W>) {
+        eprintln!("{}", record);
+    }
+}
+
+/// Writes each record as a JSON object on its own line.
+///
+/// Words and addresses are written as JSON numbers when a double holds
+/// them exactly, and as decimal strings otherwise, since most JSON readers
+/// parse numbers into doubles and would silently round wide values.
+#[derive(Debug)]
+pub struct JsonLines<O> {
+    out: O,
+}
+
+impl<O: Write> JsonLines<O> {
+    pub fn new(out: O) -> JsonLines<O> {
+        JsonLines { out }
+    }
+}
+
+impl JsonLines<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<JsonLines<BufWriter<File>>> {
+        Ok(JsonLines::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+/// Largest magnitude a double holds exactly along with every integer below it.
+const JSON_SAFE: i64 = (1 << 53) - 1;
+
+/// A word as a JSON number, or a string if a double can't hold it exactly.
+fn json_word<W: Word>(word: &W) -> String {
+    match word.to_code() {
+        Some(value) if (-JSON_SAFE..=JSON_SAFE).contains(&value) => value.to_string(),
+        _ => format!("\"{}\"", word),
+    }
+}
+
+fn json_addr(addr: usize) -> String {
+    if addr as u64 <= JSON_SAFE as u64 {
+        addr.to_string()
+    } else {
+        format!("\"{}\"", addr)
+    }
+}
+
+impl<W: Word, O: Write + Send> Tracer<W> for JsonLines<O> {
+    fn record(&mut self, record: &Record<W>) {
+        let operands: Vec<String> = record.operands.iter().map(json_word).collect();
+        let writes: Vec<String> = record
+            .writes
+            .iter()
+            .map(|(addr, value)| {
+                format!(
+                    "{{\"addr\":{},\"value\":{}}}",
+                    json_addr(*addr),
+                    json_word(value)
+                )
+            })
+            .collect();
+
+        // a failing trace sink should not take the machine down with it
+        let _ = writeln!(
+            self.out,
+            "{{\"ip\":{},\"op\":\"{}\",\"operands\":[{}],\"writes\":[{}]}}",
+            json_addr(record.ip),
+            record
+                .op
+                .to_string()
+                .replace('\\', "\\\\")
+                .replace('"', "\\\""),
+            operands.join(","),
+            writes.join(","),
+        );
+    }
+}
+
+/// Keeps the most recent records in memory.
+///
+/// Clones share the same buffer, so a handle can be kept
+/// while another is attached to the machine.
+#[derive(Debug, Clone)]
+pub struct Ring<W> {
+    capacity: usize,
+    records: Arc<Mutex<VecDeque<Record<W>>>>,
+}
+
+impl<W: Word> Ring<W> {
+    pub fn new(capacity: usize) -> Ring<W> {
+        let records = Arc::new(Mutex::new(VecDeque::with_capacity(capacity)));
+
+        Ring { capacity, records }
+    }
+
+    /// The buffered records, oldest first.
+    pub fn records(&self) -> Vec<Record<W>> {
+        let records = self.records.lock().unwrap_or_else(|err| err.into_inner());
+
+        records.iter().cloned().collect()
+    }
+}
+
+impl<W: Word> Tracer<W> for Ring<W> {
+    fn record(&mut self, record: &Record<W>) {
+        let mut records = self.records.lock().unwrap_or_else(|err| err.into_inner());
+
+        if self.capacity == 0 {
+            return;
+        }
+
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+
+        records.push_back(record.clone());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::IntCode;
+
+    #[test]
+    fn ring_keeps_latest() {
+        let prog = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let ring = Ring::new(2);
+        let mut machine: IntCode<i64> = IntCode::new(prog, vec![]);
+
+        machine.trace(ring.clone());
+        machine.run().unwrap();
+
+        let records = ring.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].ip, 4);
+        assert_eq!(records[0].operands, vec![70, 50]);
+        assert_eq!(records[0].writes, vec![(0, 3500)]);
+        assert_eq!(records[1].op, OpCode::Quit);
+
+        assert_eq!(
+            records[0].to_string(),
+            "0x0004: MULT %0x0003, %0x000b, %0x0000 (70, 50) [%0x0000 <- 3500]"
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        let prog = vec![3, 0, 4, 0, 99];
+        let ring = Ring::new(8);
+        let mut machine: IntCode<i64> = IntCode::new(prog, vec![-7]);
+
+        machine.trace(ring.clone());
+        machine.run().unwrap();
+
+        let mut out = Vec::new();
+        let mut json = JsonLines::new(&mut out);
+        for record in ring.records() {
+            json.record(&record);
+        }
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"ip":0,"op":"IN   %0x0000","operands":[],"writes":[{"addr":0,"value":-7}]}"#,
+                r#"{"ip":2,"op":"OUT  %0x0000","operands":[-7],"writes":[]}"#,
+                r#"{"ip":4,"op":"QT","operands":[],"writes":[]}"#,
+            ]
+        );
+
+        // past 2^53 words are strings, so doubles don't round them
+        let prog = vec![1, 0, 9, 9, 99, 0, 0, 0, 0, 1 << 53];
+        let ring = Ring::new(1);
+        let mut machine: IntCode<i128> = IntCode::new(prog, vec![]);
+
+        machine.trace(ring.clone());
+        machine.step().unwrap();
+
+        let mut out = Vec::new();
+        JsonLines::new(&mut out).record(&ring.records()[0]);
+
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(
+            text.lines().next(),
+            Some(
+                r#"{"ip":0,"op":"ADD  %0x0000, %0x0009, %0x0009","operands":[1,"9007199254740992"],"writes":[{"addr":9,"value":"9007199254740993"}]}"#
+            )
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use crate::{OpCode, Word};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Accesses a watchpoint stops on.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Watch {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// How a watched cell was touched.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Access {
+    Read,
+    Write,
+
+    /// A write into an instruction that has already executed.
+    Code,
+}
+
+/// A watched access, reported through `Halt::Watch`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Watchpoint<W> {
+    /// Address of the instruction that made the access.
+    pub ip: usize,
+    pub op: OpCode<W>,
+
+    pub addr: usize,
+    pub access: Access,
+
+    /// The cell's value before the instruction, and after it.
+    /// Both are the value read for reads.
+    pub old: W,
+    pub new: W,
+}
+
+impl<W: Word> Display for Watchpoint<W> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        write!(fmt, "0x{:04x}: {} ", self.ip, self.op)?;
+
+        match self.access {
+            Access::Read => write!(fmt, "read %0x{:04x} = {}", self.addr, self.old),
+            Access::Write => write!(
+                fmt,
+                "wrote %0x{:04x}: {} -> {}",
+                self.addr, self.old, self.new
+            ),
+            Access::Code => write!(
+                fmt,
+                "modified code at %0x{:04x}: {} -> {}",
+                self.addr, self.old, self.new
+            ),
+        }
+    }
+}
+
+/// Watchpoints and executed code tracking of a machine.
+#[derive(Debug, Clone)]
+pub(crate) struct Watcher<W> {
+    points: BTreeMap<usize, Watch>,
+
+    /// Cells covered by executed instructions, as disjoint ranges from
+    /// start to end, if self modifying code is being caught.
+    executed: Option<BTreeMap<usize, usize>>,
+
+    /// The access to report for the current instruction:
+    /// address, access, old and new value.
+    touch: Option<(usize, Access, W, W)>,
+}
+
+impl<W: Word> Watcher<W> {
+    pub(crate) fn new() -> Watcher<W> {
+        Watcher {
+            points: BTreeMap::new(),
+            executed: None,
+            touch: None,
+        }
+    }
+
+    pub(crate) fn active(&self) -> bool {
+        !self.points.is_empty() || self.executed.is_some()
+    }
+
+    pub(crate) fn set(&mut self, addr: usize, watch: Watch) {
+        self.points.insert(addr, watch);
+    }
+
+    pub(crate) fn clear(&mut self, addr: usize) -> bool {
+        self.points.remove(&addr).is_some()
+    }
+
+    pub(crate) fn points(&self) -> impl Iterator<Item = (usize, Watch)> + '_ {
+        self.points.iter().map(|(addr, watch)| (*addr, *watch))
+    }
+
+    pub(crate) fn watch_code(&mut self, enabled: bool) {
+        self.executed = if enabled { Some(BTreeMap::new()) } else { None };
+    }
+
+    /// Forgets executed code, e.g. because memory was replaced.
+    pub(crate) fn reset(&mut self) {
+        if let Some(executed) = &mut self.executed {
+            executed.clear();
+        }
+
+        self.touch = None;
+    }
+
+    /// Marks the cells of an instruction about to execute.
+    pub(crate) fn execute(&mut self, ip: usize, size: usize) {
+        let executed = match &mut self.executed {
+            Some(executed) => executed,
+            None => return,
+        };
+
+        let mut start = ip;
+        let mut end = ip.saturating_add(size);
+
+        if let Some((&before, &until)) = executed.range(..=start).next_back() {
+            if until >= end {
+                return;
+            }
+
+            if until >= start {
+                start = before;
+            }
+        }
+
+        // absorb every range the instruction overlaps or touches
+        while let Some((&next, &until)) = executed.range(start..=end).next() {
+            executed.remove(&next);
+            end = end.max(until);
+        }
+
+        executed.insert(start, end);
+    }
+
+    fn executed(&self, addr: usize) -> bool {
+        match &self.executed {
+            Some(executed) => {
+                matches!(executed.range(..=addr).next_back(), Some((_, &end)) if addr < end)
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn read(&mut self, addr: usize, value: &W) {
+        if self.touch.is_some() {
+            return;
+        }
+
+        if let Some(Watch::Read) | Some(Watch::ReadWrite) = self.points.get(&addr) {
+            self.touch = Some((addr, Access::Read, value.clone(), value.clone()));
+        }
+    }
+
+    /// Checks a write, which is reported over any read by the same instruction.
+    pub(crate) fn write(&mut self, addr: usize, old: &W, new: &W) {
+        if let Some((_, Access::Write, _, _)) | Some((_, Access::Code, _, _)) = self.touch {
+            return;
+        }
+
+        let access = match self.points.get(&addr) {
+            Some(Watch::Write) | Some(Watch::ReadWrite) => Access::Write,
+            _ if self.executed(addr) => Access::Code,
+            _ => return,
+        };
+
+        self.touch = Some((addr, access, old.clone(), new.clone()));
+    }
+
+    pub(crate) fn take(&mut self) -> Option<(usize, Access, W, W)> {
+        self.touch.take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Access, Halt, IntCode, Watch};
+
+    #[test]
+    fn writes() {
+        let prog = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let mut machine: IntCode<i64> = IntCode::new(prog, vec![]);
+        machine.watch(0, Watch::Write);
+
+        let hit = match machine.exec() {
+            Ok(Halt::Watch(hit)) => hit,
+            halt => panic!("unexpected {:?}", halt),
+        };
+
+        assert_eq!((hit.ip, hit.addr, hit.access), (4, 0, Access::Write));
+        assert_eq!((hit.old, hit.new), (1, 3500));
+        assert_eq!(
+            hit.to_string(),
+            "0x0004: MULT %0x0003, %0x000b, %0x0000 wrote %0x0000: 1 -> 3500"
+        );
+
+        assert_eq!(machine.ip(), 8);
+        assert_eq!(machine.exec(), Ok(Halt::Quit));
+    }
+
+    #[test]
+    fn reads_after_output() {
+        let prog = vec![4, 5, 4, 5, 99, 7];
+        let mut machine: IntCode<i64> = IntCode::new(prog, vec![]);
+        machine.watch(5, Watch::Read);
+
+        assert_eq!(machine.exec(), Ok(Halt::Output(7)));
+        match machine.exec() {
+            Ok(Halt::Watch(hit)) => assert_eq!((hit.ip, hit.access, hit.old), (0, Access::Read, 7)),
+            halt => panic!("unexpected {:?}", halt),
+        }
+
+        assert!(machine.unwatch(5));
+        assert_eq!(machine.run(), Ok(vec![7]));
+    }
+
+    #[test]
+    fn self_modifying() {
+        // overwrites its own first instruction, then jumps back to it
+        let prog = vec![1101, 98, 1, 0, 1105, 1, 0];
+        let mut machine: IntCode<i64> = IntCode::new(prog, vec![]);
+        machine.watch_code(true);
+
+        match machine.exec() {
+            Ok(Halt::Watch(hit)) => {
+                assert_eq!((hit.ip, hit.addr, hit.access), (0, 0, Access::Code));
+                assert_eq!((hit.old, hit.new), (1101, 99));
+            }
+            halt => panic!("unexpected {:?}", halt),
+        }
+
+        assert_eq!(machine.exec(), Ok(Halt::Quit));
+    }
+
+    #[test]
+    fn far_code() {
+        let far: i64 = 1 << 40;
+        let mut machine: IntCode<i64> = IntCode::new(vec![1105, 1, far], vec![]);
+
+        // writes into its own operand, then past the code that ran
+        let code = [1101, 0, 0, far + 3, 1101, 0, 0, far + 9, 99];
+        for (offset, word) in code.iter().enumerate() {
+            machine[far as usize + offset] = *word;
+        }
+        machine.watch_code(true);
+
+        match machine.exec() {
+            Ok(Halt::Watch(hit)) => assert_eq!((hit.ip, hit.access), (far as usize, Access::Code)),
+            halt => panic!("unexpected {:?}", halt),
+        }
+
+        assert_eq!(machine.exec(), Ok(Halt::Quit));
+    }
+}
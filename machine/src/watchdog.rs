@@ -0,0 +1,47 @@
+use crate::{Memory, Word};
+
+/// Brent style cycle detection over whole machine states.
+///
+/// An anchor state is saved after 1, 2, 4, 8, ... steps. Reaching the
+/// anchor again without input in between means the machine repeats forever,
+/// and any such cycle is caught within twice its length plus its start.
+#[derive(Debug, Clone)]
+pub(crate) struct LoopDetector<W> {
+    anchor: Option<(usize, W, Memory<W>)>,
+    steps: u64,
+    window: u64,
+}
+
+impl<W: Word> LoopDetector<W> {
+    pub(crate) fn new() -> LoopDetector<W> {
+        LoopDetector {
+            anchor: None,
+            steps: 1,
+            window: 1,
+        }
+    }
+
+    /// Forgets the anchor, e.g. because consumed input changed the machine's future.
+    pub(crate) fn reset(&mut self) {
+        *self = LoopDetector::new();
+    }
+
+    /// Records the state about to execute, returning whether it repeats the anchor.
+    pub(crate) fn check(&mut self, ip: usize, rb: &W, mem: &Memory<W>) -> bool {
+        if let Some((anchor_ip, anchor_rb, anchor_mem)) = &self.anchor {
+            if *anchor_ip == ip && anchor_rb == rb && anchor_mem == mem {
+                return true;
+            }
+        }
+
+        if self.steps == self.window {
+            self.anchor = Some((ip, rb.clone(), mem.clone()));
+            self.window *= 2;
+            self.steps = 0;
+        }
+
+        self.steps += 1;
+
+        false
+    }
+}
@@ -0,0 +1,119 @@
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::ops::{Add, Mul};
+use std::str::FromStr;
+
+/// A memory cell of an `IntCode` machine.
+///
+/// Implemented for `i32`, `i64`, `i128` and, with the `bigint` feature,
+/// `num_bigint::BigInt`.
+pub trait Word:
+    Clone
+    + Send
+    + Sync
+    + 'static
+    + Debug
+    + Display
+    + FromStr
+    + PartialEq
+    + PartialOrd
+    + From<i32>
+    + Add<Output = Self>
+    + Mul<Output = Self>
+{
+    /// The word as a memory address, if it is a valid one.
+    fn to_addr(&self) -> Option<usize>;
+
+    /// A memory address as a word, if it fits.
+    fn from_addr(addr: usize) -> Option<Self>;
+
+    /// The word as an instruction code, if it fits.
+    fn to_code(&self) -> Option<i64>;
+
+    /// The sum, if it does not overflow.
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+
+    /// The product, if it does not overflow.
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+
+    /// Writes the word as signed hex, e.g. `0x002a` or `-0x0003`.
+    fn fmt_hex(&self, fmt: &mut Formatter<'_>) -> FmtResult;
+}
+
+macro_rules! prim_word {
+    ($($ty: ty),*) => {
+        $(
+            impl Word for $ty {
+                fn to_addr(&self) -> Option<usize> {
+                    usize::try_from(*self).ok()
+                }
+
+                fn from_addr(addr: usize) -> Option<Self> {
+                    <$ty>::try_from(addr).ok()
+                }
+
+                fn to_code(&self) -> Option<i64> {
+                    i64::try_from(*self).ok()
+                }
+
+                fn checked_add(&self, other: &Self) -> Option<Self> {
+                    <$ty>::checked_add(*self, *other)
+                }
+
+                fn checked_mul(&self, other: &Self) -> Option<Self> {
+                    <$ty>::checked_mul(*self, *other)
+                }
+
+                fn fmt_hex(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+                    if *self < 0 {
+                        write!(fmt, "-0x{:04x}", self.unsigned_abs())
+                    } else {
+                        write!(fmt, "0x{:04x}", self)
+                    }
+                }
+            }
+        )*
+    };
+}
+
+prim_word!(i32, i64, i128);
+
+#[cfg(feature = "bigint")]
+impl Word for num_bigint::BigInt {
+    fn to_addr(&self) -> Option<usize> {
+        usize::try_from(self).ok()
+    }
+
+    fn from_addr(addr: usize) -> Option<Self> {
+        Some(num_bigint::BigInt::from(addr))
+    }
+
+    fn to_code(&self) -> Option<i64> {
+        i64::try_from(self).ok()
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn fmt_hex(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        if self.sign() == num_bigint::Sign::Minus {
+            write!(fmt, "-0x{:04x}", self.magnitude())
+        } else {
+            write!(fmt, "0x{:04x}", self.magnitude())
+        }
+    }
+}
+
+/// Displays a word through `Word::fmt_hex`.
+pub struct Hex<'a, W>(pub &'a W);
+
+impl<W: Word> Display for Hex<'_, W> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        self.0.fmt_hex(fmt)
+    }
+}